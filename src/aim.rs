@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{bullet::Bullet, player::PlayerControlled};
+
+/// Moving further than this in one tick is a wrap around the screen, not speed
+const MAX_PLAYER_STEP: f32 = 50.;
+
+/// How an enemy gun points its shots
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum Aim {
+    /// Straight down along the bullet paths
+    #[default]
+    Fixed,
+    /// Turned to where the player is when fired
+    AtPlayer,
+    /// Turned to where the player will be by the time the bullets get there, going by how they are moving
+    Lead,
+    /// Fired at the player and keep turning towards them (at most `turn_rate` radians a second) until `lifetime` seconds are up
+    Homing { turn_rate: f32, lifetime: f32 },
+}
+
+/// Where the player is and how far they moved last tick, for enemies to shoot at
+#[derive(Resource, Default)]
+pub struct PlayerTarget {
+    pub position: Option<Vec2>,
+    pub velocity: Vec2,
+}
+
+pub fn reset_target(mut target: ResMut<PlayerTarget>){
+    *target = PlayerTarget::default();
+}
+
+pub fn track_player(mut target: ResMut<PlayerTarget>, player: Query<&Transform, With<PlayerControlled>>){
+    let position = player.get_single().ok().map(|transform| transform.translation.truncate());
+
+    let step = match (target.position, position) {
+        (Some(last), Some(now)) => now - last,
+        _ => Vec2::ZERO,
+    };
+    target.velocity = if step.length() > MAX_PLAYER_STEP { Vec2::ZERO } else { step };
+    target.position = position;
+}
+
+/// Rotation (for `Vec2::rotate`) that turns straight down to point from `from` at `to`
+pub fn turn_towards(from: Vec2, to: Vec2) -> Vec2 {
+    let dir = (to - from).normalize_or_zero();
+    if dir == Vec2::ZERO { return Vec2::X; }
+    Vec2::new(-dir.y, dir.x)
+}
+
+impl Aim {
+    /// Point a freshly fired enemy bullet according to this aim, leaves it alone when there is no player to aim at
+    pub fn apply(&self, bullet: Bullet, from: Vec2, target: &PlayerTarget) -> Bullet {
+        let Some(position) = target.position else { return bullet; };
+
+        match *self {
+            Aim::Fixed => bullet,
+            Aim::AtPlayer => bullet.turned(turn_towards(from, position)),
+            Aim::Lead => {
+                // ticks it takes the bullet to cover the distance at its starting speed
+                let ticks = from.distance(position) / bullet.first_step().max(1.);
+                bullet.turned(turn_towards(from, position + target.velocity * ticks))
+            },
+            Aim::Homing { turn_rate, lifetime } => bullet.turned(turn_towards(from, position)).homing(turn_rate, lifetime),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use bevy::{math::bounding::BoundingCircle, prelude::*};
+
+use crate::{
+    bullet::{self, Bullet, CollisionEvent, ScoreEvent}, bullet_pool::BulletPool, enemy, explosion::Explode, hitbox::Hitbox,
+    player::PlayerControlled, replay::{PlayerButton, PlayerInput}, sfx::{PlaySfx, Sfx}
+};
+
+/// Bombs the player starts a run with
+const STARTING_BOMBS: u32 = 2;
+pub const MAX_BOMBS: u32 = 5;
+const BLAST_RADIUS: f32 = 300.;
+const BLAST_DAMAGE: i64 = 400;
+/// Score for every enemy bullet the bomb clears
+const BULLET_SCORE: u64 = 5;
+/// Seconds nothing can hurt the player after a bomb
+const INVULNERABLE_TIME: f32 = 2.5;
+
+/// Bombs the player has left
+#[derive(Component)]
+pub struct Bombs(u32);
+
+impl Default for Bombs {
+    fn default() -> Self {
+        Bombs(STARTING_BOMBS)
+    }
+}
+
+impl Bombs {
+    pub fn get(&self) -> u32 { self.0 }
+
+    pub fn add(&mut self, bombs: u32) {
+        self.0 = (self.0 + bombs).min(MAX_BOMBS);
+    }
+}
+
+/// Kept out of the spatial grid so nothing can hit it until the timer runs out
+#[derive(Component)]
+pub struct Invulnerable(Timer);
+
+impl Invulnerable {
+    pub fn new(seconds: f32) -> Invulnerable {
+        Invulnerable(Timer::new(Duration::from_secs_f32(seconds), TimerMode::Once))
+    }
+
+    /// Make it last at least `seconds` more, a longer one already running is kept
+    pub fn extend(&mut self, seconds: f32) {
+        if self.0.remaining_secs() < seconds {
+            *self = Invulnerable::new(seconds);
+        }
+    }
+}
+
+/// A bomb going off at a position
+#[derive(Event)]
+pub struct BombBlast(pub Vec2);
+
+/// Set off a bomb when the player asks for one and has any left
+pub fn use_bomb(
+    input: Res<PlayerInput>,
+    mut commands: Commands,
+    mut player: Query<(Entity, &Transform, &mut Bombs, Option<&mut Invulnerable>), With<PlayerControlled>>,
+    mut blasts: EventWriter<BombBlast>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut explosions: EventWriter<Explode>
+){
+    if !input.just_pressed(PlayerButton::Bomb) { return; }
+    let Ok((entity, transform, mut bombs, invulnerable)) = player.get_single_mut() else { return; };
+    if bombs.0 == 0 { return; }
+
+    bombs.0 -= 1;
+    match invulnerable {
+        // don't cut a respawn's invulnerability short
+        Some(mut invulnerable) => invulnerable.extend(INVULNERABLE_TIME),
+        None => { commands.entity(entity).insert(Invulnerable::new(INVULNERABLE_TIME)); },
+    }
+    blasts.send(BombBlast(transform.translation.truncate()));
+    sfx.send(PlaySfx(Sfx::Womp));
+    explosions.send(Explode(transform.translation, Color::WHITE));
+}
+
+/// Clear every enemy bullet (for score) and hit the enemies caught in the blast
+pub fn detonate_bombs(
+    mut blasts: EventReader<BombBlast>,
+    mut bullets: Query<(Entity, &mut Bullet, &mut Visibility)>,
+    enemies: Query<(Entity, &Transform, &Hitbox), With<enemy::Collider>>,
+    mut pool: ResMut<BulletPool>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut score_events: EventWriter<ScoreEvent>
+){
+    for blast in blasts.read() {
+        let mut cleared = 0;
+        for (entity, mut bullet, mut visibility) in &mut bullets {
+            if bullet.is_player() || !bullet.is_active() { continue; }
+            pool.release(entity, &mut bullet, &mut visibility);
+            cleared += 1;
+        }
+        score_events.send(ScoreEvent(cleared * BULLET_SCORE, 0));
+
+        let area = BoundingCircle::new(blast.0, BLAST_RADIUS);
+        for (entity, transform, hitbox) in &enemies {
+            if bullet::explosion_collision(area, hitbox.volume(transform).aabb()).is_some() {
+                collision_events.send(CollisionEvent(entity, BLAST_DAMAGE, false));
+            }
+        }
+    }
+}
+
+pub fn tick_invulnerability(time: Res<Time>, mut commands: Commands, mut query: Query<(Entity, &mut Invulnerable)>){
+    for (entity, mut invulnerable) in &mut query {
+        invulnerable.0.tick(time.delta());
+        if invulnerable.0.finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+/// Flash the player while nothing can hurt them
+pub fn blink_invulnerable(time: Res<Time>, mut player: Query<(&mut Visibility, Has<Invulnerable>), With<PlayerControlled>>){
+    for (mut visibility, invulnerable) in &mut player {
+        let hidden = invulnerable && (time.elapsed_seconds() * 30.).sin() < 0.;
+        *visibility = if hidden { Visibility::Hidden } else { Visibility::Inherited };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extend_keeps_the_longer_timer() {
+        let mut invulnerable = Invulnerable::new(3.);
+        invulnerable.extend(2.5);
+        assert_eq!(invulnerable.0.remaining_secs(), 3.);
+
+        invulnerable.0.tick(Duration::from_secs(1));
+        invulnerable.extend(2.5);
+        assert_eq!(invulnerable.0.remaining_secs(), 2.5);
+    }
+}
@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    bullet::{Bullet, Plasma}, bullet_pool::BulletPool, enemy::{Enemy, EnemyBundle, WaveTimer},
+    enemy_defs::{BossDef, BossPhaseDef, BurstShape, EnemyDefs, EnemyDefsHandle}, explosion::Explode, game::{GameRng, ScoreBoard},
+    health::Health, playfield::Playfield, power_ups, sfx::{PlaySfx, Sfx}, shapes::{generate_circle, generate_square, generate_triangle}
+};
+
+/// A boss shows up every this many waves
+const BOSS_WAVE_INTERVAL: u64 = 5;
+
+#[derive(Component)]
+pub struct Boss {
+    name: String,
+    phases: Vec<BossPhaseDef>,
+    phase: usize,
+    burst_timer: Timer,
+}
+
+impl Boss {
+    fn new(name: String, phases: Vec<BossPhaseDef>) -> Boss {
+        let burst_timer = burst_timer(&phases[0]);
+        Boss { name, phases, phase: 0, burst_timer }
+    }
+
+    /// Phase the boss should be in with this much health left, each phase gets an even share of the pool
+    fn phase_for(&self, health: &Health) -> usize {
+        let lost = 1. - health.get_health().max(0) as f32 / health.get_max_health().max(1) as f32;
+        ((lost * self.phases.len() as f32) as usize).min(self.phases.len() - 1)
+    }
+}
+
+fn burst_timer(phase: &BossPhaseDef) -> Timer {
+    let delay = phase.burst.as_ref().map(|burst| burst.delay).unwrap_or(1.);
+    Timer::new(Duration::from_secs_f32(delay), TimerMode::Repeating)
+}
+
+/// Bosses fought this run and whether one is alive right now (waves hold off until it is dead)
+#[derive(Resource, Default)]
+pub struct BossTracker {
+    encounters: u32,
+    active: bool,
+}
+
+/// Spawn the next boss once the wave count reaches a milestone
+#[allow(clippy::too_many_arguments)]
+pub fn boss_spawner(
+    mut commands: Commands,
+    mut tracker: ResMut<BossTracker>,
+    mut wave_timer: ResMut<WaveTimer>,
+    mut sfx: EventWriter<PlaySfx>,
+    score_board: Res<ScoreBoard>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>,
+    playfield: Res<Playfield>
+){
+    let next_wave = BOSS_WAVE_INTERVAL * (tracker.encounters as u64 + 1);
+    if tracker.active || score_board.get_waves() < next_wave { return; }
+
+    let Some(defs) = defs.get(&defs_handle.0) else { return; };
+    if defs.bosses.is_empty() { return; }
+
+    // bosses take turns, and every time around they have another health pool
+    let def = &defs.bosses[tracker.encounters as usize % defs.bosses.len()];
+    let health_scale = (tracker.encounters as usize / defs.bosses.len()) as i64 + 1;
+
+    spawn_boss(&mut commands, &mut sfx, &mut tracker, &mut wave_timer, Vec2::new(0., playfield.top + 150.), def, health_scale);
+}
+
+/// Bring a boss in and hold the waves until it is dead
+pub fn spawn_boss(
+    commands: &mut Commands,
+    sfx: &mut EventWriter<PlaySfx>,
+    tracker: &mut BossTracker,
+    wave_timer: &mut WaveTimer,
+    position: Vec2,
+    def: &BossDef,
+    health_scale: i64
+){
+    if def.phases.is_empty() { return; }
+
+    commands.spawn((EnemyBundle::new_boss(position.x, position.y, def, health_scale), Boss::new(def.name.clone(), def.phases.clone())));
+    sfx.send(PlaySfx(Sfx::Womp));
+
+    tracker.encounters += 1;
+    tracker.active = true;
+    wave_timer.0.pause();
+}
+
+/// Start the waves again once the boss is dead and drop some power ups
+pub fn boss_defeated(
+    mut commands: Commands,
+    boss_query: Query<(), With<Boss>>,
+    mut tracker: ResMut<BossTracker>,
+    mut wave_timer: ResMut<WaveTimer>,
+    mut rng: ResMut<GameRng>
+){
+    // the kill itself was scored like any other enemy, the reward for it is in the boss's score and multiplier
+    if tracker.active && boss_query.is_empty() {
+        tracker.active = false;
+        wave_timer.0.unpause();
+        power_ups::spawn_powerup_wave(&mut commands, &mut rng);
+    }
+}
+
+/// Move bosses on to their next phase as they lose health and fire their bursts
+pub fn boss_control(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut boss_query: Query<(&Transform, &Health, &mut Enemy, &mut Boss)>,
+    mut pool: ResMut<BulletPool>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut explosions: EventWriter<Explode>,
+    playfield: Res<Playfield>
+){
+    for (transform, health, mut enemy, mut boss) in &mut boss_query {
+        let phase = boss.phase_for(health);
+        if phase > boss.phase {
+            boss.phase = phase;
+            let def = boss.phases[phase].clone();
+            enemy.set_pattern(def.path.clone(), def.shot_delay, def.gun());
+            boss.burst_timer = burst_timer(&def);
+
+            sfx.send(PlaySfx(Sfx::Warp));
+            explosions.send(Explode(transform.translation, Color::GOLD));
+        }
+
+        // hold fire until it has dropped onto the screen
+        if transform.translation.y > playfield.top { continue; }
+
+        boss.burst_timer.tick(time.delta());
+        if !boss.burst_timer.just_finished() { continue; }
+        let Some(burst) = &boss.phases[boss.phase].burst else { continue; };
+
+        let (x, y) = (transform.translation.x, transform.translation.y);
+        let points = match burst.shape {
+            BurstShape::Circle => generate_circle(x, y, burst.size / 2., burst.bullets),
+            BurstShape::Square => generate_square(x, y, burst.size, burst.bullets / 4),
+            BurstShape::Triangle => generate_triangle((x - burst.size / 2., y), (x + burst.size / 2., y), (x, y - burst.size / 2.), burst.bullets / 3),
+        };
+
+        sfx.send(PlaySfx(Sfx::ShieldHit));
+        for p in points {
+            pool.spawn(&mut commands, p.0, p.1, Bullet::new(-1, burst.path.clone(), 0., false, burst.damage), Plasma::Purple);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct BossBar;
+
+#[derive(Component)]
+pub struct BossBarFill;
+
+#[derive(Component)]
+pub struct BossBarLabel;
+
+/// Health bar across the top of the screen, hidden while there is no boss
+pub fn spawn_boss_bar(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(80.),
+                    left: Val::Percent(25.),
+                    width: Val::Percent(50.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(6.),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            BossBar
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle { font: asset_server.load("fonts/EvilEmpire.otf"), font_size: 32., color: Color::GOLD }),
+                BossBarLabel
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style { width: Val::Percent(100.), height: Val::Px(14.), ..default() },
+                    background_color: Color::rgba(0.2, 0.2, 0.2, 0.8).into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn((
+                        NodeBundle {
+                            style: Style { width: Val::Percent(100.), height: Val::Percent(100.), ..default() },
+                            background_color: Color::CRIMSON.into(),
+                            ..default()
+                        },
+                        BossBarFill
+                    ));
+                });
+        });
+}
+
+pub fn update_boss_bar(
+    boss_query: Query<(&Health, &Boss)>,
+    mut bar_query: Query<&mut Visibility, With<BossBar>>,
+    mut fill_query: Query<&mut Style, With<BossBarFill>>,
+    mut label_query: Query<&mut Text, With<BossBarLabel>>
+){
+    let boss = boss_query.iter().next();
+
+    for mut visibility in &mut bar_query {
+        *visibility = if boss.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+    }
+    let Some((health, boss)) = boss else { return; };
+
+    let left = health.get_health().max(0) as f32 / health.get_max_health().max(1) as f32;
+    for mut style in &mut fill_query {
+        style.width = Val::Percent(left * 100.);
+    }
+    for mut text in &mut label_query {
+        text.sections[0].value = format!("{}  {}/{}", boss.name, boss.phase + 1, boss.phases.len());
+    }
+}
@@ -1,16 +1,17 @@
-use bevy::{math::bounding::{Aabb2d, BoundingCircle, IntersectsVolume}, prelude::*};
-use bevy_hanabi::{EffectProperties, EffectSpawner};
+use bevy::{math::bounding::{Aabb2d, BoundingCircle, IntersectsVolume}, prelude::*, utils::HashSet};
 
-use crate::{enemy, game::ScoreBoard, gun, health, player::{self, PlayerControlled}};
-use super::{T_BOUND, B_BOUND, L_BOUND, R_BOUND};
+use rand::Rng;
+
+use crate::{aim::{turn_towards, PlayerTarget}, bomb::Invulnerable, bullet_pool::BulletPool, enemy, explosion::Explode, game::{Difficulty, GameRng, ScoreBoard}, gun, health, hitbox::Hitbox, lives::PlayerDown, player::PlayerControlled, playfield::Playfield, sfx::{PlaySfx, Sfx}, spatial::{Layer, SpatialGrid}, spawner::{self, Brood}, trajectory::Trajectory};
 
 const BULLET_DEATH: f32 = 5.;
 
 #[derive(Event)]
-pub struct CollisionEvent(Entity, i64, bool);
+/// Damage for an entity: (**target**, **damage**, **is the player**)
+pub struct CollisionEvent(pub Entity, pub i64, pub bool);
 
 #[derive(Event)]
-pub struct ScoreEvent(u64, u64);  // add to score on a event (add this event to event queue when a unit dies if it is not the player???)
+pub struct ScoreEvent(pub u64, pub u64);  // add to score on a event (add this event to event queue when a unit dies if it is not the player???)
 
 
 #[derive(Component)]
@@ -18,40 +19,116 @@ pub struct ScoreEvent(u64, u64);  // add to score on a event (add this event to
 pub struct Bullet
 {
     dir: i8,
-    fx: fn(f32) -> f32,
-    fy: fn(f32) -> f32,
+    path: Trajectory,
     tick: f32,
     ply: bool,
-    damage: i64
+    damage: i64,
+    active: bool,
+    /// Rotation applied to every step (`Vec2::X` leaves the path as it is)
+    turn: Vec2,
+    /// Turn rate in radians a second for bullets that home in on the player
+    homing: Option<f32>,
+    /// Seconds until the bullet fizzles out
+    lifetime: f32,
+    /// Has been inside the player's graze radius
+    near: bool,
+    grazed: bool
 } // 
 
 impl Bullet{
     /// Update the life time of the bullet 
     /// 
-    pub fn new(dir: i8, fy: fn(f32) -> f32, fx: fn(f32) -> f32, tick: f32, ply: bool, damage: i64) -> Bullet {
-        Bullet {dir: dir, fx: fx, fy: fy, tick: tick, ply: ply, damage: damage}
+    pub fn new(dir: i8, path: Trajectory, tick: f32, ply: bool, damage: i64) -> Bullet {
+        Bullet {dir: dir, path: path, tick: tick, ply: ply, damage: damage, active: true, turn: Vec2::X, homing: None, lifetime: BULLET_DEATH, near: false, grazed: false}
+    }
+
+    /// Fly the path rotated, `turn` is what straight down gets turned into (see `aim::turn_towards`)
+    pub fn turned(mut self, turn: Vec2) -> Bullet {
+        self.turn = turn;
+        self
+    }
+
+    /// Keep turning towards the player by at most `turn_rate` radians a second, fizzling out after `lifetime` seconds
+    pub fn homing(mut self, turn_rate: f32, lifetime: f32) -> Bullet {
+        self.homing = Some(turn_rate);
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// How far the bullet moves on its first tick
+    pub fn first_step(&self) -> f32 {
+        self.path.eval(self.tick).length()
+    }
+
+    /// Fired by the player
+    pub fn is_player(&self) -> bool {
+        self.ply
+    }
+
+    /// Note the bullet came within the player's graze radius
+    pub fn pass_near(&mut self) {
+        self.near = true;
+    }
+
+    /// Count the graze once the bullet is past the player, false if it never came close or already counted
+    pub fn graze(&mut self) -> bool {
+        let counts = self.near && !self.grazed;
+        self.grazed |= counts;
+        counts
+    }
+
+    /// False while the bullet is hidden in the pool
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn deactivate(&mut self) {
+        self.active = false;
     }
     pub fn update(&mut self, time:f32) {
         self.tick += time;
     }
 }
 
+#[derive(Component, Clone, Copy, PartialEq)]
+/// Which plasma texture a bullet is drawn with (the renderer keeps them loaded)
+pub enum Plasma {
+    Blue,
+    Green,
+    Red,
+    Purple,
+}
+
+impl Plasma {
+    pub const ALL: [Plasma; 4] = [Plasma::Blue, Plasma::Green, Plasma::Red, Plasma::Purple];
+
+    pub fn asset(&self) -> &'static str {
+        match self {
+            Plasma::Blue => "plasma_blue.png",
+            Plasma::Green => "plasma_green.png",
+            Plasma::Red => "plasma_red.png",
+            Plasma::Purple => "plasma_purple.png",
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct BulletBundle {
-    sprite_bundle: SpriteBundle,
+    spatial_bundle: SpatialBundle,
+    plasma: Plasma,
+    hitbox: Hitbox,
     bullet: Bullet,
 }
 
 /// Bundle to contain the bullet class
 impl BulletBundle{
-    /// Create a new bullet by passing a spawn position, bullet data (direction, fx, fy, and tick start), and a texture image from the Asset loader
-    pub fn new(spawn_x: f32, spawn_y: f32, bullet: Bullet, asset: Handle<Image>) -> BulletBundle {
+    /// Create a new bullet by passing a spawn position, bullet data (direction, path, and tick start), and the plasma to draw it with
+    /// (fire through `BulletPool::spawn` instead so used up bullets get reused)
+    pub fn new(spawn_x: f32, spawn_y: f32, bullet: Bullet, plasma: Plasma) -> BulletBundle {
         BulletBundle {
-            sprite_bundle: SpriteBundle {
-                texture: asset,
-                transform: Transform::from_xyz(spawn_x, spawn_y, 0.),
-                ..default()
-            },
+            spatial_bundle: SpatialBundle::from_transform(Transform::from_xyz(spawn_x, spawn_y, 0.)),
+            plasma,
+            hitbox: Hitbox::bullet(bullet.ply),
             bullet: bullet
         }
     }
@@ -60,79 +137,77 @@ impl BulletBundle{
 }
 
 
-/// Move the bullets 
-pub fn bullet_movement(
-    time: Res<Time>, 
-    mut bullet_query: Query<(Entity, &mut Bullet, &mut Transform), (With<Bullet>, Without<enemy::Collider>)>,
-    collider_query: Query<(Entity, &Transform), (With<enemy::Collider>, Without<Bullet>)>,
-    player_query: Query<(Entity, &Transform), (With<player::PlayerControlled>, Without<Bullet>, Without<enemy::Collider>)>,
-    mut commands: Commands,
-    mut collision_events: EventWriter<CollisionEvent>,
-    mut scoreboard: ResMut<ScoreBoard>
-) {
-    for (bullet_entity, mut bullet,  mut b_transform) in &mut bullet_query { // move each bullet 
-        // Move the bullet
-        if bullet.tick > BULLET_DEATH || b_transform.translation.y < B_BOUND || b_transform.translation.y > (T_BOUND + 64) as f32 {
-            commands.entity(bullet_entity).despawn();
+/// Bullets past their lifetime or off the top / bottom of the playfield go back to the pool, player bullets wrap across the sides
+pub fn bullet_bounds(
+    mut bullet_query: Query<(Entity, &mut Bullet, &mut Transform, &mut Visibility), Without<enemy::Collider>>,
+    mut pool: ResMut<BulletPool>,
+    playfield: Res<Playfield>
+){
+    for (bullet_entity, mut bullet, mut b_transform, mut visibility) in &mut bullet_query {
+        if !bullet.active { continue; }
+
+        if bullet.tick > bullet.lifetime || b_transform.translation.y < playfield.bottom || b_transform.translation.y > playfield.top + 64. {
+            pool.release(bullet_entity, &mut bullet, &mut visibility);
             continue;
         }
 
-        if b_transform.translation.x < 0. - L_BOUND as f32 && bullet.ply {
-            b_transform.translation.x = R_BOUND as f32 - 1.;
-        }
-        if b_transform.translation.x > R_BOUND as f32 && bullet.ply {
-            b_transform.translation.x = 0. - L_BOUND as f32 + 1.; 
+        if bullet.ply {
+            if let Some(x) = playfield.wrap_x(b_transform.translation.x) { b_transform.translation.x = x; }
         }
+    }
+}
 
+/// Move the bullets and add them to the spatial grid, used up bullets go back to the pool
+pub fn bullet_movement(
+    time: Res<Time>, 
+    mut bullet_query: Query<(Entity, &mut Bullet, &mut Transform, &mut Visibility, &Hitbox), (With<Bullet>, Without<enemy::Collider>)>,
+    mut grid: ResMut<SpatialGrid>,
+    mut pool: ResMut<BulletPool>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut scoreboard: ResMut<ScoreBoard>,
+    target: Res<PlayerTarget>
+) {
+    for (bullet_entity, mut bullet,  mut b_transform, mut visibility, hitbox) in &mut bullet_query { // move each bullet 
+        if !bullet.active { continue; }
+
+        // Move the bullet
         bullet.update(time.delta_seconds());
         
-        b_transform.translation.y += (bullet.fy)(bullet.tick) * bullet.dir as f32; // run the y function
-        b_transform.translation.x += (bullet.fx)(bullet.tick) * bullet.dir as f32; // run the x function
-
-        //gizmos.rect_2d(b_transform.translation.truncate(), 0., Vec2::new(16., 16.), Color::rgb(1.,0.,0.));
-
-        match bullet.ply {
-            false => { // check bullet collision with player
-                if let Ok((p_ent, p_transform)) = player_query.get_single() {
-                    let collision = bullet_collision(Aabb2d::new(b_transform.translation.truncate(), Vec2::new(8.,8.)), Aabb2d::new(p_transform.translation.truncate(), Vec2::new(16.,16.)));
-                    
-                    if let Some(_) = collision { // collision between enemy and player bullet
-                        // want to fire sound here  
-                        collision_events.send(CollisionEvent(p_ent, bullet.damage, true));
-                        scoreboard.set_mul(0); // reset player multiplier when they are hit
-                        commands.entity(bullet_entity).despawn(); // despawn the bullet 
-                    }
-                }
-            }
-            true => {
-                // Check bullet collision with enemy
-                for(collider_entity, e_transform) in &collider_query {
-                    let collision = bullet_collision(Aabb2d::new(b_transform.translation.truncate(), b_transform.scale.truncate()/2.), Aabb2d::new(e_transform.translation.truncate(), Vec2::new(16.,16.)));
-                    
-                    if let Some(_) = collision { // collision between enemy and player bullet
-                        collision_events.send(CollisionEvent(collider_entity, bullet.damage, false));
-                        commands.entity(bullet_entity).despawn(); // despawn the bullet 
-                    }
+        if let (Some(turn_rate), Some(position)) = (bullet.homing, target.position) {
+            let heading = bullet.turn.rotate(Vec2::NEG_Y);
+            let wanted = turn_towards(b_transform.translation.truncate(), position).rotate(Vec2::NEG_Y);
+            let max_turn = turn_rate * time.delta_seconds();
+            let angle = heading.angle_between(wanted).clamp(-max_turn, max_turn);
+            bullet.turn = Vec2::from_angle(angle).rotate(bullet.turn);
+        }
+
+        let step = bullet.turn.rotate(bullet.path.eval(bullet.tick) * bullet.dir as f32); // run the x / y functions
+        b_transform.translation.y += step.y;
+        b_transform.translation.x += step.x;
+
+        let volume = hitbox.volume(&b_transform);
+        let mut hit = false;
+        // bullets running into each other is up to `bullet_on_bullet_collision`
+        for layer in [Layer::Player, Layer::Enemy].into_iter().filter(|layer| hitbox.mask.contains(*layer)) {
+            for (target, _) in grid.query(layer, volume) {
+                let is_player = layer == Layer::Player;
+                collision_events.send(CollisionEvent(target, bullet.damage, is_player));
+                if is_player {
+                    scoreboard.set_mul(0); // reset player multiplier when they are hit
                 }
+                hit = true;
             }
         }
-        
-    }
-}
 
-/// Check if a bullet has intersected a enemy / bounding box 
-pub fn bullet_collision(bullet: Aabb2d, enemy: Aabb2d) -> Option<bool> {
-    if bullet.intersects(&enemy) {
-        return Some(true)
-    }else {
-        return None
+        if hit {
+            pool.release(bullet_entity, &mut bullet, &mut visibility);
+        } else {
+            grid.insert(bullet_entity, hitbox.layer, volume);
+        }
     }
-
-    
 }
 
-
-fn explosion_collision(circle: BoundingCircle, square: Aabb2d) -> Option<bool> {
+pub fn explosion_collision(circle: BoundingCircle, square: Aabb2d) -> Option<bool> {
     if circle.intersects(&square) {
         return Some(true)
     }else {
@@ -143,67 +218,56 @@ fn explosion_collision(circle: BoundingCircle, square: Aabb2d) -> Option<bool> {
 
 //Its really not good im doing all this inside this function lmao
 /// Event for processing damage
+#[allow(clippy::too_many_arguments)]
 pub fn apply_collision_damage(
-    mut health_query: Query<&mut health::Health>,
+    mut health_query: Query<(&mut health::Health, Has<Invulnerable>)>,
     mut collision_events: EventReader<CollisionEvent>,
     mut commands: Commands,
     mut score_events: EventWriter<ScoreEvent>,
-    enemy_query: Query<(Entity, &enemy::Enemy, &Transform), (With<enemy::Collider>, Without<EffectProperties>)>,
-    //gun_query: Query<(&mut gun::Gun, &mut shapes::ShapeGun), With<player::PlayerControlled>>,
-    mut effect: Query<(
-        &mut EffectProperties,
-        &mut EffectSpawner,
-        &mut Transform,
-    )>,
-    asset_server: Res<AssetServer>
+    enemy_query: Query<(&enemy::Enemy, &Transform, Option<&Brood>), With<enemy::Collider>>,
+    grid: Res<SpatialGrid>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut explosions: EventWriter<Explode>,
+    mut rng: ResMut<GameRng>,
+    mut player_downs: EventWriter<PlayerDown>,
+    difficulty: Res<Difficulty>
 ){
     if !collision_events.is_empty() {
+        let mut dead = HashSet::new(); // several bullets can hit the same thing in one tick, it only dies once
         // This prevents events staying active on the next frame.
         for dmg in collision_events.read() {
-            if let Ok(mut health) = health_query.get_mut(dmg.0) {
-                health.damage(dmg.1);
+            if dead.contains(&dmg.0) { continue; }
+            if let Ok((mut health, invulnerable)) = health_query.get_mut(dmg.0) {
+                if invulnerable { continue; } // hits sent before it respawned
+                let damage = if dmg.2 { (dmg.1 as f32 * difficulty.damage_taken()) as i64 } else { dmg.1 };
+                health.damage(damage);
                 
 
                 if !health.is_alive() { // Entity has died from damage
+                    dead.insert(dmg.0);
                     //check if we should add score
                     if !dmg.2 { // not a player dying 
                         
 
-                        if let Ok((_, en, transform)) = enemy_query.get(dmg.0) { // enemy killed 
+                        if let Ok((en, transform, brood)) = enemy_query.get(dmg.0) { // enemy killed 
 
-                            commands.spawn(AudioBundle {
-                                source: asset_server.load("sounds/hit.wav"),
-                                // auto-despawn the entity when playback finishes
-                                settings: PlaybackSettings::DESPAWN
-                            });
+                            sfx.send(PlaySfx(Sfx::Hit));
 
-                            let (score, mul) = en.get_type().get_score();
+                            let (score, mul) = en.get_score();
                             score_events.send(ScoreEvent(score, mul));
 
+                            let color = Color::lch(1., 1., rng.0.gen::<f32>() * 120.);
+                            explosions.send(Explode(transform.translation, color));
 
-                            let Ok((
-                                mut properties,
-                                mut spawner,
-                                mut effect_transform,
-                            )) = effect.get_single_mut()
-                            else {
-                                warn!("effect not ready yet, returning");
-                                return;
-                            };
-
-                            let color = Color::lch(1., 1., rand::random::<f32>() * 120.);
-                            properties.set(
-                                "spawn_color",
-                                color.as_linear_rgba_u32().into(),
-                            );
-
-                            effect_transform.translation = transform.translation;
-                            spawner.reset(); // spawn the effect 
+                            if let Some(brood) = brood { // spawners split in two (consequences)
+                                spawner::split(&mut commands, brood, transform.translation);
+                            }
 
 
-                            for (e, _, effected_transform) in enemy_query.iter() {
-                                if let Some(_) = explosion_collision(BoundingCircle::new(transform.translation.truncate(), 32.), Aabb2d::new(effected_transform.translation.truncate(), effect_transform.scale.truncate()/2.)){
-                                    let Ok(mut e_health) = health_query.get_mut(e) else {return;};
+                            let blast = BoundingCircle::new(transform.translation.truncate(), 32.);
+                            for (e, bounds) in grid.query(Layer::Enemy, blast.aabb_2d()) {
+                                if let Some(_) = explosion_collision(blast, Aabb2d::new(bounds.center(), Vec2::splat(0.5))){
+                                    let Ok((mut e_health, _)) = health_query.get_mut(e) else { continue; };
                                     e_health.damage(20);
                                 }
                             }
@@ -211,7 +275,11 @@ pub fn apply_collision_damage(
                         
                     }    
 
-                    commands.entity(dmg.0).despawn(); // despawn
+                    if dmg.2 {
+                        player_downs.send(PlayerDown); // the player loses a life instead
+                    } else {
+                        commands.entity(dmg.0).despawn(); // despawn
+                    }
                 }
             }
             
@@ -245,35 +313,26 @@ pub fn update_score(
 
 
 pub fn bullet_on_bullet_collision(
-    mut commands: Commands,
-    bullet_query: Query<(Entity, &mut Bullet, &mut Transform)>,
+    mut bullet_query: Query<(Entity, &mut Bullet, &mut Visibility, &Transform, &Hitbox)>,
+    grid: Res<SpatialGrid>,
+    mut pool: ResMut<BulletPool>
 )
 {   
-    // 
-    let mut ply_bullets = Vec::new();
-    let mut en_bullets = Vec::new();
+    let mut hits = Vec::new();
+    for (en, bul, _, tran, hitbox) in bullet_query.iter() {
+        if !hitbox.mask.contains(Layer::EnemyBullet) || !bul.active { continue; }
 
-    for (en, bul, transform) in bullet_query.iter() {
-        if bul.ply {ply_bullets.push((en,bul,transform))} else {en_bullets.push((en,bul,transform));}
+        // first enemy bullet this one runs into
+        if let Some((en2, _)) = grid.query(Layer::EnemyBullet, hitbox.volume(tran)).first() {
+            hits.push((en, *en2));
+        }
     }
 
-    for (en, _, tran) in ply_bullets {
-        for(en2, _, tran2) in &en_bullets{
-            
- 
-            let collision = bullet_collision(Aabb2d::new(tran.translation.truncate(), Vec2::new(8.,8.)), Aabb2d::new(tran2.translation.truncate(), Vec2::new(8.,8.)));
-
-            if let Some(_) = collision { // collision between enemy and player bullet
-                commands.entity(*en2).despawn(); // despawn the bullet 
-                commands.entity(en).despawn(); // despawn the bullet 
-                break; // exit this loop so we iterate out of the outer after removing its bullet
+    for (en, en2) in hits {
+        for ent in [en2, en] { // both bullets go back to the pool
+            if let Ok((_, mut bul, mut visibility, _, _)) = bullet_query.get_mut(ent) {
+                pool.release(ent, &mut bul, &mut visibility);
             }
-            continue;
-
         }
     }
 }
-
-
-
-
@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+use crate::{bullet::{Bullet, BulletBundle, Plasma}, hitbox::Hitbox};
+
+/// Bullet entities that have been used up and are hidden, waiting to be fired again.
+/// Every shot should go through `spawn` so bursts reuse entities instead of spawning / despawning them
+#[derive(Resource, Default)]
+pub struct BulletPool {
+    free: Vec<Entity>,
+}
+
+impl BulletPool {
+    /// Fire a bullet from (x, y), reusing a hidden bullet when there is one
+    pub fn spawn(&mut self, commands: &mut Commands, x: f32, y: f32, bullet: Bullet, plasma: Plasma) {
+        match self.free.pop() {
+            // same components as a new bullet so the entity doesn't move between archetypes
+            Some(entity) => { commands.entity(entity).insert((Hitbox::bullet(bullet.is_player()), bullet, Transform::from_xyz(x, y, 0.), Visibility::Inherited, plasma)); },
+            None => { commands.spawn(BulletBundle::new(x, y, bullet, plasma)); },
+        }
+    }
+
+    /// Take a bullet out of play, does nothing if it already was
+    pub fn release(&mut self, entity: Entity, bullet: &mut Bullet, visibility: &mut Visibility) {
+        if !bullet.is_active() { return; }
+
+        bullet.deactivate();
+        *visibility = Visibility::Hidden;
+        self.free.push(entity);
+    }
+}
@@ -0,0 +1,304 @@
+use bevy::{asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext}, prelude::*, utils::BoxedFuture};
+use serde::Deserialize;
+
+use crate::{
+    boss::{self, BossTracker}, enemy::{Enemy, EnemyBundle, WaveTimer}, enemy_defs::{BossDef, EnemyDef, EnemyDefs, EnemyDefsHandle},
+    formation::{self, Formation, GroupDef}, game::{GameRng, GameTimer, ScoreBoard}, playfield::Playfield, sfx::{PlaySfx, Sfx}, trajectory::Trajectory, GameState
+};
+
+pub const CAMPAIGN_PATH: &str = "campaign/campaign.campaign.ron";
+
+/// Levels played one after the other in campaign mode, loaded from `assets/campaign/campaign.campaign.ron`
+#[derive(Asset, TypePath, Deserialize)]
+pub struct Campaign {
+    pub levels: Vec<WaveScript>,
+}
+
+/// One level: timed spawn events and what it takes to clear it
+#[derive(Deserialize, Clone)]
+pub struct WaveScript {
+    pub name: String,
+    pub complete: Completion,
+    pub events: Vec<SpawnEvent>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum Completion {
+    /// Everything in the script has spawned and been killed
+    ClearAll,
+    /// Stay alive this many seconds into the level
+    Survive(f32),
+    /// Get the score up to this (counted over the whole campaign)
+    Score(u64),
+}
+
+#[derive(Deserialize, Clone)]
+pub enum Spawn {
+    /// An enemy from the definitions file by name
+    Enemy(String),
+    /// A boss from the definitions file by name
+    Boss(String),
+}
+
+impl Spawn {
+    fn name(&self) -> &str {
+        match self {
+            Spawn::Enemy(name) | Spawn::Boss(name) => name,
+        }
+    }
+}
+
+/// A group of enemies brought in together
+#[derive(Deserialize, Clone)]
+pub struct SpawnEvent {
+    /// Seconds after the level starts
+    pub at: f32,
+    pub spawn: Spawn,
+    #[serde(default = "one")]
+    pub count: usize,
+    #[serde(default)]
+    pub formation: Formation,
+    /// Where the formation is placed
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default = "formation_top")]
+    pub y: f32,
+    /// Seconds between one member of the group and the next showing up
+    #[serde(default)]
+    pub stagger: f32,
+    /// Path to fly in on instead of the enemy's own
+    #[serde(default)]
+    pub path: Option<Trajectory>,
+    /// Keep the enemies together, moving the whole formation along a path (they all show up at once)
+    #[serde(default)]
+    pub group: Option<GroupDef>,
+}
+
+fn one() -> usize { 1 }
+
+fn formation_top() -> f32 { Playfield::default().top - 50. }
+
+#[derive(Default)]
+pub struct CampaignLoader;
+
+impl AssetLoader for CampaignLoader {
+    type Asset = Campaign;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Campaign, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let campaign = ron::de::from_bytes::<Campaign>(&bytes)?;
+            if campaign.levels.is_empty() {
+                return Err("the campaign has no levels".into());
+            }
+            Ok(campaign)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campaign.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct CampaignHandle(pub Handle<Campaign>);
+
+#[derive(Clone)]
+enum Spawnable {
+    Enemy(EnemyDef),
+    Boss(BossDef),
+    Group(EnemyDef, usize, Formation, GroupDef),
+}
+
+/// One enemy of the current level waiting for its time to come
+struct QueuedSpawn {
+    at: f32,
+    what: Spawnable,
+    position: Vec2,
+    path: Option<Trajectory>,
+}
+
+/// How far through the campaign this run is
+#[derive(Resource, Default)]
+pub struct CampaignRun {
+    level: usize,
+    levels: usize,
+    started: bool,
+    name: String,
+    complete: Option<Completion>,
+    /// Seconds since the current level started
+    time: f32,
+    queue: Vec<QueuedSpawn>,
+    cleared: bool,
+}
+
+impl CampaignRun {
+    /// Levels beaten so far and how many there are
+    pub fn progress(&self) -> (usize, usize) { (self.level, self.levels) }
+
+    pub fn cleared(&self) -> bool { self.cleared }
+
+    pub fn level_name(&self) -> &str { &self.name }
+
+    pub fn level_time(&self) -> f32 { self.time }
+}
+
+/// Queue up everything the next level spawns (positions are rolled now so a level always plays out the same for a seed)
+#[allow(clippy::too_many_arguments)]
+pub fn load_level(
+    mut run: ResMut<CampaignRun>,
+    mut rng: ResMut<GameRng>,
+    campaigns: Res<Assets<Campaign>>,
+    campaign_handle: Res<CampaignHandle>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>,
+    playfield: Res<Playfield>,
+    mut game_state: ResMut<NextState<GameState>>
+){
+    if run.started || run.cleared { return; }
+    let Some(defs) = defs.get(&defs_handle.0) else { return; };
+    // the campaign is loaded before any run starts, so if it isn't there it failed to load
+    let Some(campaign) = campaigns.get(&campaign_handle.0).filter(|campaign| run.level < campaign.levels.len()) else {
+        warn!("there is no campaign level {} to play, back to the menu", run.level + 1);
+        game_state.set(GameState::Menu);
+        return;
+    };
+    let script = &campaign.levels[run.level];
+
+    let mut queue = Vec::new();
+    for event in &script.events {
+        let what = match &event.spawn {
+            Spawn::Enemy(name) => defs.get(name).cloned().map(Spawnable::Enemy),
+            Spawn::Boss(name) => defs.bosses.iter().find(|def| def.name == *name).cloned().map(Spawnable::Boss),
+        };
+        let Some(what) = what else {
+            warn!("level {} spawns {} which isn't in the enemy definitions", script.name, event.spawn.name());
+            continue;
+        };
+
+        if let (Some(group), Spawnable::Enemy(def)) = (&event.group, &what) {
+            queue.push(QueuedSpawn {
+                at: event.at,
+                what: Spawnable::Group(def.clone(), event.count, event.formation.clone(), group.clone()),
+                position: Vec2::new(event.x, event.y),
+                path: None,
+            });
+            continue;
+        }
+
+        for i in 0..event.count {
+            queue.push(QueuedSpawn {
+                at: event.at + event.stagger * i as f32,
+                what: what.clone(),
+                position: event.formation.position(i, event.count, Vec2::new(event.x, event.y), &mut rng, &playfield),
+                path: event.path.clone(),
+            });
+        }
+    }
+    queue.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+    info!("level {}: {}", run.level + 1, script.name);
+    run.levels = campaign.levels.len();
+    run.name = script.name.clone();
+    run.complete = Some(script.complete);
+    run.time = 0.;
+    run.queue = queue;
+    run.started = true;
+}
+
+/// Bring in everything the level has scheduled up to now
+pub fn campaign_spawner(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut run: ResMut<CampaignRun>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut tracker: ResMut<BossTracker>,
+    mut wave_timer: ResMut<WaveTimer>
+){
+    if !run.started { return; }
+    run.time += time.delta_seconds();
+
+    let due = run.queue.iter().take_while(|spawn| spawn.at <= run.time).count();
+    if due == 0 { return; }
+
+    sfx.send(PlaySfx(Sfx::Warp));
+    for spawn in run.queue.drain(..due) {
+        match spawn.what {
+            Spawnable::Enemy(def) => {
+                let mut bundle = EnemyBundle::new(spawn.position.x, spawn.position.y, &def);
+                if let Some(path) = spawn.path {
+                    bundle.enemy.set_path(path);
+                }
+                commands.spawn(bundle);
+            },
+            Spawnable::Boss(def) => boss::spawn_boss(&mut commands, &mut sfx, &mut tracker, &mut wave_timer, spawn.position, &def, 1),
+            Spawnable::Group(def, count, formation, group) => formation::spawn_group(&mut commands, &def, count, &formation, spawn.position, &group),
+        }
+    }
+}
+
+/// Move on once the level is done, the run ends after the last one
+pub fn campaign_progress(
+    mut run: ResMut<CampaignRun>,
+    enemies: Query<(), With<Enemy>>,
+    mut score_board: ResMut<ScoreBoard>,
+    game_time: Res<GameTimer>,
+    mut game_state: ResMut<NextState<GameState>>
+){
+    let Some(complete) = run.complete.filter(|_| run.started) else { return; };
+
+    let done = match complete {
+        Completion::ClearAll => run.queue.is_empty() && enemies.is_empty(),
+        Completion::Survive(seconds) => run.time >= seconds,
+        Completion::Score(score) => score_board.get_score() >= score,
+    };
+    if !done { return; }
+
+    score_board.increment_wave(); // a campaign counts levels as waves
+    run.level += 1;
+    run.started = false;
+
+    if run.level >= run.levels {
+        info!("campaign cleared");
+        run.cleared = true;
+        score_board.set_game_time(game_time.0.elapsed_secs());
+        game_state.set(GameState::Menu);
+    }
+}
+
+#[derive(Component)]
+pub struct LevelBanner;
+
+const BANNER_TIME: f32 = 3.;
+
+pub fn spawn_level_banner(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font: asset_server.load("fonts/EvilEmpire.otf"), font_size: 80., color: Color::GOLD })
+            .with_text_justify(JustifyText::Center)
+            .with_style(Style { position_type: PositionType::Absolute, top: Val::Percent(40.), width: Val::Percent(100.), justify_content: JustifyContent::Center, ..default() }),
+        LevelBanner
+    ));
+}
+
+/// Show the level's name for its first few seconds
+pub fn update_level_banner(run: Res<CampaignRun>, mut banner: Query<&mut Text, With<LevelBanner>>){
+    let value = if run.started && run.level_time() < BANNER_TIME {
+        format!("Level {}\n{}", run.level + 1, run.level_name())
+    } else {
+        String::new()
+    };
+
+    for mut text in &mut banner {
+        if text.sections[0].value != value {
+            text.sections[0].value.clone_from(&value);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::{path::PathBuf, str::FromStr};
+
+use bevy::log::error;
+
+use crate::game::GameMode;
+
+/// Command line options (parsed by hand, there are only a couple)
+#[derive(Default)]
+pub struct Args {
+    /// Run the simulation without a window / gpu / audio
+    pub headless: bool,
+    /// Stop the headless run after this many fixed ticks
+    pub frames: Option<u64>,
+    /// Seed for the run (random when not given)
+    pub seed: Option<u64>,
+    /// Play this replay file back instead of reading the keyboard
+    pub replay: Option<PathBuf>,
+    /// Where to save the replay of each run
+    pub record: Option<PathBuf>,
+    /// Play the campaign instead of endless survival
+    pub campaign: bool,
+    /// Keep this many extra bullets alive in a headless run and report tick times (collision benchmark)
+    pub stress: Option<usize>,
+}
+
+impl Args {
+    /// Parse the process arguments, anything it doesn't understand is logged and the process exits with an error
+    pub fn parse() -> Args {
+        match Args::parse_from(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(e) => {
+                // bevy's logger only comes up with the app, this is before there is one
+                bevy::log::tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+                error!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    fn parse_from(arguments: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut args = Args::default();
+        let mut iter = arguments.into_iter();
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--headless" => args.headless = true,
+                "--frames" => args.frames = Some(value(&arg, iter.next())?),
+                "--seed" => args.seed = Some(value(&arg, iter.next())?),
+                "--replay" => args.replay = Some(value(&arg, iter.next())?),
+                "--record" => args.record = Some(value(&arg, iter.next())?),
+                "--campaign" => args.campaign = true,
+                "--stress" => args.stress = Some(value(&arg, iter.next())?),
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+
+        Ok(args)
+    }
+
+    pub fn mode(&self) -> GameMode {
+        if self.campaign { GameMode::Campaign } else { GameMode::Endless }
+    }
+}
+
+/// The value following a flag
+fn value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value.parse().map_err(|_| format!("{} can't be {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, String> {
+        Args::parse_from(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn flags_and_values() {
+        let args = parse("--headless --frames 600 --seed 42 --replay a.bhr --record b.bhr --campaign --stress 5000").unwrap();
+        assert!(args.headless && args.campaign);
+        assert_eq!((args.frames, args.seed, args.stress), (Some(600), Some(42), Some(5000)));
+        assert_eq!(args.replay, Some(PathBuf::from("a.bhr")));
+        assert_eq!(args.record, Some(PathBuf::from("b.bhr")));
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert_eq!(parse("--headless --fast").err(), Some(String::from("unknown argument --fast")));
+        assert_eq!(parse("--seed").err(), Some(String::from("--seed needs a value")));
+        assert_eq!(parse("--frames lots").err(), Some(String::from("--frames can't be lots")));
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::{BTreeMap, HashSet};
+
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{menu::{MenuState, MenuView}, storage};
+
+const CONTROLS_FILE: &str = "controls.ron";
+/// How far a stick / trigger has to be pushed to count as pressed
+const AXIS_THRESHOLD: f32 = 0.5;
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+/// Everything the player can do, gameplay reads these instead of keys or buttons
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Boost,
+    Fire,
+    Special,
+    Reload,
+    Bomb,
+    VolumeUp,
+    VolumeDown,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Action::Left, Action::Right, Action::Up, Action::Down, Action::Boost, Action::Fire,
+        Action::Special, Action::Reload, Action::Bomb, Action::VolumeUp, Action::VolumeDown, Action::Pause,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Action::Left => "Move Left",
+            Action::Right => "Move Right",
+            Action::Up => "Move Up",
+            Action::Down => "Move Down",
+            Action::Boost => "Boost",
+            Action::Fire => "Shoot",
+            Action::Special => "Special",
+            Action::Reload => "Reload",
+            Action::Bomb => "Bomb",
+            Action::VolumeUp => "Volume Up",
+            Action::VolumeDown => "Volume Down",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+/// A key, gamepad button or gamepad axis pushed one way
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Button(GamepadButtonType),
+    Axis(GamepadAxisType, AxisDirection),
+}
+
+impl Binding {
+    /// Both keys, both gamepad buttons or both gamepad axes
+    fn same_kind(&self, other: &Binding) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => key_name(*key),
+            Binding::Button(button) => format!("Pad {:?}", button),
+            Binding::Axis(axis, AxisDirection::Positive) => format!("Pad {:?}+", axis),
+            Binding::Axis(axis, AxisDirection::Negative) => format!("Pad {:?}-", axis),
+        }
+    }
+}
+
+/// Short name of a key, `KeyW` is just `W`
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")).unwrap_or(&name).to_string()
+}
+
+/// Which keys / buttons trigger each action, saved in `controls.ron` in the data dir
+#[derive(Resource, Serialize, Deserialize, Clone)]
+pub struct Bindings(BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::*;
+        use AxisDirection::*;
+
+        Bindings(BTreeMap::from([
+            (Action::Left, vec![Key(KeyCode::KeyA), Axis(GamepadAxisType::LeftStickX, Negative), Button(GamepadButtonType::DPadLeft)]),
+            (Action::Right, vec![Key(KeyCode::KeyD), Axis(GamepadAxisType::LeftStickX, Positive), Button(GamepadButtonType::DPadRight)]),
+            (Action::Up, vec![Key(KeyCode::KeyW), Axis(GamepadAxisType::LeftStickY, Positive), Button(GamepadButtonType::DPadUp)]),
+            (Action::Down, vec![Key(KeyCode::KeyS), Axis(GamepadAxisType::LeftStickY, Negative), Button(GamepadButtonType::DPadDown)]),
+            (Action::Boost, vec![Key(KeyCode::ShiftLeft), Button(GamepadButtonType::LeftTrigger)]),
+            (Action::Fire, vec![Key(KeyCode::Space), Button(GamepadButtonType::South), Button(GamepadButtonType::RightTrigger2)]),
+            (Action::Special, vec![Key(KeyCode::KeyE), Button(GamepadButtonType::East)]),
+            (Action::Reload, vec![Key(KeyCode::KeyR), Button(GamepadButtonType::West)]),
+            (Action::Bomb, vec![Key(KeyCode::KeyQ), Button(GamepadButtonType::North)]),
+            (Action::VolumeUp, vec![Key(KeyCode::ArrowUp)]),
+            (Action::VolumeDown, vec![Key(KeyCode::ArrowDown)]),
+            (Action::Pause, vec![Key(KeyCode::Escape), Button(GamepadButtonType::Start)]),
+        ]))
+    }
+}
+
+impl Bindings {
+    /// Saved bindings, any action missing from the file (or the whole file) gets the default
+    pub fn load() -> Bindings {
+        let mut bindings = Bindings::default();
+        if let Some(saved) = storage::load_ron::<Bindings>(CONTROLS_FILE) {
+            bindings.0.extend(saved.0);
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        storage::save_ron(CONTROLS_FILE, self);
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map(|b| b.as_slice()).unwrap_or(&[])
+    }
+
+    /// Bind an action, replacing the first binding it had of the same kind (key, button or axis) and keeping the rest
+    fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        if bindings.contains(&binding) { return; }
+
+        match bindings.iter_mut().find(|b| b.same_kind(&binding)) {
+            Some(old) => *old = binding,
+            None => bindings.push(binding),
+        }
+    }
+
+    /// The keyboard keys bound to an action, for help text
+    pub fn keys(&self, action: Action) -> String {
+        let keys: Vec<String> = self.get(action).iter().filter_map(|b| match b { Binding::Key(key) => Some(key_name(*key)), _ => None }).collect();
+        if keys.is_empty() { String::from("(unbound)") } else { keys.join(" or ") }
+    }
+
+    fn label(&self, action: Action) -> String {
+        let bound: Vec<String> = self.get(action).iter().map(|b| b.label()).collect();
+        format!("{}: {}", action.label(), if bound.is_empty() { String::from("-") } else { bound.join(", ") })
+    }
+}
+
+/// Actions held this frame, filled in from the keyboard and every connected gamepad
+#[derive(Resource, Default)]
+pub struct ActionState { held: HashSet<Action>, just_pressed: HashSet<Action> }
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+/// Action currently waiting for a new key / button on the controls screen
+#[derive(Resource, Default)]
+struct Rebinding(Option<Action>);
+
+#[derive(Component)]
+struct RebindButton(Action);
+
+pub fn controls_plugin(app: &mut App) {
+    app
+        .insert_resource(Bindings::load())
+        .init_resource::<ActionState>()
+        .init_resource::<Rebinding>()
+        .add_systems(PreUpdate, update_actions.after(InputSystem))
+        .add_systems(OnEnter(MenuState::Controls), spawn_controls)
+        .add_systems(OnExit(MenuState::Controls), |mut rebinding: ResMut<Rebinding>| rebinding.0 = None)
+        .add_systems(Update, (
+            open_controls.run_if(in_state(MenuState::Main)),
+            (close_controls, rebind_buttons, capture_binding, update_binding_text).chain().run_if(in_state(MenuState::Controls))
+        ));
+}
+
+fn binding_held(binding: &Binding, keys: &ButtonInput<KeyCode>, gamepads: &Gamepads, buttons: &ButtonInput<GamepadButton>, axes: &Axis<GamepadAxis>) -> bool {
+    match binding {
+        Binding::Key(key) => keys.pressed(*key),
+        Binding::Button(button) => gamepads.iter().any(|pad| buttons.pressed(GamepadButton::new(pad, *button))),
+        Binding::Axis(axis, direction) => gamepads.iter().any(|pad| {
+            let value = axes.get(GamepadAxis::new(pad, *axis)).unwrap_or(0.);
+            match direction {
+                AxisDirection::Positive => value > AXIS_THRESHOLD,
+                AxisDirection::Negative => value < -AXIS_THRESHOLD,
+            }
+        }),
+    }
+}
+
+fn update_actions(
+    bindings: Res<Bindings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut state: ResMut<ActionState>
+){
+    let held: HashSet<Action> = Action::ALL.into_iter()
+        .filter(|action| bindings.get(*action).iter().any(|b| binding_held(b, &keys, &gamepads, &buttons, &axes)))
+        .collect();
+
+    state.just_pressed = held.difference(&state.held).copied().collect();
+    state.held = held;
+}
+
+fn open_controls(keyboard_input: Res<ButtonInput<KeyCode>>, mut menu_state: ResMut<NextState<MenuState>>){
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        menu_state.set(MenuState::Controls);
+    }
+}
+
+fn close_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    rebinding: Res<Rebinding>,
+    mut bindings: ResMut<Bindings>,
+    mut menu_state: ResMut<NextState<MenuState>>
+){
+    if rebinding.0.is_some() { return; }
+
+    if keyboard_input.any_just_pressed([KeyCode::KeyC, KeyCode::Escape]) {
+        menu_state.set(MenuState::Main);
+    }
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        *bindings = Bindings::default();
+        bindings.save();
+    }
+}
+
+fn rebind_buttons(
+    mut interaction_query: Query<(&Interaction, &RebindButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut rebinding: ResMut<Rebinding>
+){
+    for (interaction, button, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                rebinding.0 = Some(button.0);
+            }
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+}
+
+/// Give the waiting action the next key, gamepad button or stick push (Escape cancels)
+fn capture_binding(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<Bindings>
+){
+    let Some(action) = rebinding.0 else { return; };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        return;
+    }
+
+    let mut captured = keys.get_just_pressed().next().map(|key| Binding::Key(*key))
+        .or_else(|| buttons.get_just_pressed().next().map(|button| Binding::Button(button.button_type)));
+
+    if captured.is_none() {
+        for pad in gamepads.iter() {
+            for axis in [GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY] {
+                let value = axes.get(GamepadAxis::new(pad, axis)).unwrap_or(0.);
+                if value.abs() > AXIS_THRESHOLD {
+                    let direction = if value > 0. { AxisDirection::Positive } else { AxisDirection::Negative };
+                    captured = Some(Binding::Axis(axis, direction));
+                }
+            }
+        }
+    }
+
+    if let Some(binding) = captured {
+        bindings.rebind(action, binding);
+        bindings.save();
+        rebinding.0 = None;
+    }
+}
+
+fn update_binding_text(
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    buttons: Query<(&RebindButton, &Children)>,
+    mut text_query: Query<&mut Text>
+){
+    if !bindings.is_changed() && !rebinding.is_changed() { return; }
+
+    for (button, children) in &buttons {
+        let Ok(mut text) = text_query.get_mut(children[0]) else { continue; };
+        text.sections[0].value = if rebinding.0 == Some(button.0) {
+            format!("{}: press a key or button (Escape to cancel)", button.0.label())
+        } else {
+            bindings.label(button.0)
+        };
+    }
+}
+
+fn spawn_controls(mut commands: Commands, asset_server: Res<AssetServer>, bindings: Res<Bindings>){
+    let font = asset_server.load("fonts/EvilEmpire.otf");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                ..default()
+            },
+            MenuView
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Controls", TextStyle { font: font.clone(), font_size: 80.0, color: Color::GOLD }));
+
+            for action in Action::ALL {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(900.0),
+                                height: Val::Px(50.0),
+                                padding: UiRect::left(Val::Px(16.0)),
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        },
+                        RebindButton(action)
+                    ))
+                    .with_children(|button_parent| {
+                        button_parent.spawn(TextBundle::from_section(
+                            bindings.label(action),
+                            TextStyle { font: font.clone(), font_size: 32.0, color: Color::rgb(0.9, 0.9, 0.9) },
+                        ));
+                    });
+            }
+
+            parent.spawn(TextBundle::from_section(
+                "Click an action to rebind it, Delete to reset everything, C or Escape to go back",
+                TextStyle { font: font.clone(), font_size: 30.0, color: Color::GRAY },
+            ));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebind_replaces_one_binding_of_the_same_kind() {
+        let mut bindings = Bindings::default();
+
+        bindings.rebind(Action::Left, Binding::Axis(GamepadAxisType::RightStickX, AxisDirection::Negative));
+        assert_eq!(bindings.get(Action::Left), [
+            Binding::Key(KeyCode::KeyA),
+            Binding::Axis(GamepadAxisType::RightStickX, AxisDirection::Negative),
+            Binding::Button(GamepadButtonType::DPadLeft),
+        ]);
+
+        bindings.rebind(Action::Fire, Binding::Button(GamepadButtonType::RightTrigger));
+        assert_eq!(bindings.get(Action::Fire), [
+            Binding::Key(KeyCode::Space),
+            Binding::Button(GamepadButtonType::RightTrigger),
+            Binding::Button(GamepadButtonType::RightTrigger2),
+        ]);
+
+        bindings.rebind(Action::VolumeUp, Binding::Button(GamepadButtonType::DPadUp));
+        assert_eq!(bindings.get(Action::VolumeUp), [Binding::Key(KeyCode::ArrowUp), Binding::Button(GamepadButtonType::DPadUp)]);
+
+        // binding what is already there changes nothing
+        bindings.rebind(Action::VolumeUp, Binding::Key(KeyCode::ArrowUp));
+        assert_eq!(bindings.get(Action::VolumeUp), [Binding::Key(KeyCode::ArrowUp), Binding::Button(GamepadButtonType::DPadUp)]);
+    }
+
+    #[test]
+    fn keys_follow_rebinding() {
+        let mut bindings = Bindings::default();
+        assert_eq!(bindings.keys(Action::Up), "W");
+        assert_eq!(bindings.keys(Action::Boost), "ShiftLeft");
+
+        bindings.rebind(Action::Up, Binding::Key(KeyCode::ArrowUp));
+        assert_eq!(bindings.keys(Action::Up), "ArrowUp");
+
+        bindings.0.insert(Action::Bomb, vec![Binding::Button(GamepadButtonType::North)]);
+        assert_eq!(bindings.keys(Action::Bomb), "(unbound)");
+        bindings.0.insert(Action::Fire, vec![Binding::Key(KeyCode::Space), Binding::Key(KeyCode::Digit1)]);
+        assert_eq!(bindings.keys(Action::Fire), "Space or 1");
+    }
+
+    #[test]
+    fn bindings_serde_round_trip() {
+        let mut bindings = Bindings::default();
+        bindings.rebind(Action::Bomb, Binding::Axis(GamepadAxisType::RightStickY, AxisDirection::Positive));
+        bindings.rebind(Action::Pause, Binding::Key(KeyCode::KeyP));
+
+        let text = ron::ser::to_string_pretty(&bindings, ron::ser::PrettyConfig::default()).unwrap();
+        let loaded: Bindings = ron::from_str(&text).unwrap();
+
+        for action in Action::ALL {
+            assert_eq!(loaded.get(action), bindings.get(action), "{:?}", action);
+        }
+    }
+}
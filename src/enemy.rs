@@ -1,47 +1,28 @@
 use std::{f32::consts::PI, time::Duration};
 
-use bevy::prelude::*;
+use bevy::{math::bounding::BoundingCircle, prelude::*};
 use rand::Rng;
+use serde::Deserialize;
 
-use crate::{bullet, game::{GameTimer, ScoreBoard}, gun, health, power_ups, shapes::{generate_circle, generate_line, generate_square, generate_triangle}, B_BOUND};
+use crate::{aim::PlayerTarget, bullet::{self, Plasma}, bullet_pool::BulletPool, enemy_defs::{BossDef, EnemyDef, EnemyDefs, EnemyDefsHandle}, explosion::Explode, formation::FormationMember, hitbox::Hitbox, game::{GameMode, GameRng, GameTimer, ScoreBoard}, gun, health, power_ups, render::SpriteAsset, sfx::{PlaySfx, Sfx}, shapes::{generate_circle, generate_line, generate_square, generate_triangle}, spatial::{Layer, SpatialGrid}, playfield::Playfield, trajectory::{self, Trajectory}};
 
-use super::T_BOUND;
 
-const L_BOUND: u16 = 500;
-const R_BOUND: u16 = 500;
 
 
 #[derive(Resource)]
 pub struct PowerUpTimer(pub Timer);
 
 
-// Paths
-const MELEE_PATH: EnemyPath = EnemyPath(|_| 0., |y| y*y / 30. );
-const LINEAR_PATH: EnemyPath = EnemyPath(|_| 2. , |_| 0.5 );
-const SPAMMER_PATH: EnemyPath = EnemyPath(|_| 0.75, |y| y.cos() + 0.2 );
-const WAVY_PATH: EnemyPath = EnemyPath(|_| 0.5, |y| 3.*y.cos() + 0.1 );
-const SPAWNER_PATH: EnemyPath = EnemyPath(|_| 0.1, |y| (3.0*y).cos() + (1./(10.*y)));
-
-// Shot delays
-const LINEAR_DELAY: (f32, f32) = (0.5, 2.5);
-const SPAMMER_DELAY: (f32, f32) = (0.5, 1.5);
-const SPAWNER_DELAY: (f32, f32) = (2.5, 9.5);
-
-// GUN + BULLET BLUEPRINTS   
-const GUN_BLUEPRINT_LINEAR: gun::GunBluePrint = gun::GunBluePrint(1.25, 20, 1, 1000, 2.0);
-const GUN_BLUEPRINT_WAVY: gun::GunBluePrint = gun::GunBluePrint(1.5, 100, 2, 1000, 2.0);
-const GUN_BLUEPRINT_SPAMMER: gun::GunBluePrint = gun::GunBluePrint(0.75, 10, 4, 3000, 2.0);
-
-const BULLET_STRAIGHT: gun::BulletBlueprint = gun::BulletBlueprint(-1, |_| 5., |_| 0., 0., false, 20);
-const BULLET_COS_POS: gun::BulletBlueprint = gun::BulletBlueprint(-1, |_| 2., |_| 0., 0., false, 20);
-const BULLET_COS_NEG: gun::BulletBlueprint = gun::BulletBlueprint(-1, |_| 2., |_| 0., 0., false, 20);
-const BULLET_DAIG_POS_0: gun::BulletBlueprint = gun::BulletBlueprint(-1, |_| 8., |_| 4., 0., false, 20);
-const BULLET_DAIG_POS_1: gun::BulletBlueprint = gun::BulletBlueprint(-1, |_| 4., |_| 8., 0., false, 20);
-const BULLET_DAIG_NEG_0: gun::BulletBlueprint = gun::BulletBlueprint(-1, |_| 8., |_| -4., 0., false, 20);
-const BULLET_DAIG_NEG_1: gun::BulletBlueprint = gun::BulletBlueprint(-1, |_| 4., |_| -8., 0., false, 20);
-
 const DEFAULT_FALL_SPEED: f32 = 20.;
 
+// Kamikaze constants
+const MELEE_SPEED: f32 = 220.; // top speed chasing the player (pixels a second)
+const MELEE_STEER: f32 = 360.; // how fast it can change its velocity (pixels a second, a second)
+const MELEE_BLAST_RADIUS: f32 = 80.;
+const MELEE_BLAST_DAMAGE: i64 = 150; // to the player and every enemy caught in the blast
+const CONTACT_DAMAGE: i64 = 60; // any other enemy running into the player
+const CONTACT_COOLDOWN: f32 = 0.5; // seconds before the same enemy can hurt the player again
+
 // Wave constants
 const WAVE_SIZE: u32 = 10; // multiplied by time elapsed (in minutes)
 const WAVE_INTERVAL: f32 = 45.;  // divided by time elapsed (in minutes)
@@ -58,38 +39,30 @@ pub struct CollisionEvent;
 #[derive(Resource)]
 pub struct WaveTimer(pub Timer);
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, Deserialize)]
 /// Enemy type enum to determine movement / combat patterns
 pub enum EnemyType {
     Melee, // Chase the player attempt to kamakazi them
     Linear, // shoot a straight shot either directly infront or at the player (maybe even fucking random)
     Wavy, // Shoot some cos/sin variant shot 
     Spammer, // shoot massive amounts of shit 
-    Spawner, // Shoot some bursts but primarily spawn more of the other types of enemies when killed spawn 2 spawners lol (consequences)
+    Spawner, // Shoot some bursts but primarily spawn more of the other types of enemies, when killed spawn 2 spawners lol (consequences), see the spawner module
+    Boss, // Shoots its gun like the others, its phases and bursts are run by the boss module
 }
 
-impl EnemyType {
-    pub fn get_score(&self) -> (u64, u64){
-        match *self {
-            EnemyType::Spawner => {(500, 3)},
-            EnemyType::Spammer => {(400, 2)},
-            EnemyType::Wavy => {(300, 1)},
-            _ => {(50, 0)}
-        }
-    }
-}
-
-/// Container to make paths easier (shoulda done this for bullets too honestly)
-pub struct EnemyPath(fn(f32)->f32, fn(f32)->f32);
-
 #[derive(Component)]
 pub struct Enemy {
+    /// Name of the definition it was spawned from
+    name: String,
     t: EnemyType,
     tick: f32,
     last_shot: f32,
-    x_path: fn(f32) -> f32,
-    y_path: fn(f32) -> f32,
+    path: Trajectory,
     shot_range: (f32, f32),
+    score: (u64, u64),
+    /// Only used by chasing (melee) enemies, pixels a second
+    velocity: Vec2,
+    contact_cooldown: f32,
     pub gun: gun::Gun
 }
 
@@ -97,42 +70,111 @@ impl Enemy{
     pub fn get_type(&self) -> EnemyType {
         self.t
     }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Score and multiplier the player gets for killing this enemy
+    pub fn get_score(&self) -> (u64, u64) {
+        self.score
+    }
+
+    /// Follow a different path from here on (starting over from t = 0)
+    pub fn set_path(&mut self, path: Trajectory) {
+        self.path = path;
+        self.tick = 0.;
+    }
+
+    /// Switch to a new movement path and gun (the path starts over from t = 0)
+    pub fn set_pattern(&mut self, path: Trajectory, shot_range: (f32, f32), gun: gun::Gun) {
+        self.set_path(path);
+        self.shot_range = shot_range;
+        self.gun = gun;
+    }
+
+    /// Steer towards `target`, returning how far to move this tick
+    fn chase(&mut self, position: Vec2, target: Vec2, delta: f32) -> Vec2 {
+        let wanted = (target - position).normalize_or_zero() * MELEE_SPEED;
+        self.velocity += (wanted - self.velocity).clamp_length_max(MELEE_STEER * delta);
+        self.velocity * delta
+    }
 }
 
 
 #[derive(Bundle)]
 pub struct EnemyBundle {
-    sprite_bundle: SpriteBundle,
+    spatial_bundle: SpatialBundle,
+    sprite: SpriteAsset,
     pub enemy: Enemy,
     collider: Collider,
+    hitbox: Hitbox,
     health: health::Health
 }
 
-/// Create a new enemey providing a spawn location, type and asset to render
+/// Create a new enemey providing a spawn location and its definition
 impl EnemyBundle {
-    pub fn new(spawn_x: f32, spawn_y: f32, t: EnemyType, asset: Handle<Image>, health: health::Health, path: EnemyPath, shot_range: (f32,f32), gun: gun::Gun) -> EnemyBundle{
+    pub fn new(spawn_x: f32, spawn_y: f32, def: &EnemyDef) -> EnemyBundle{
+        let starting_bullets = def.bullets.iter().map(|b| b.blueprint()).collect();
+
         EnemyBundle {
-            sprite_bundle: SpriteBundle {
-                texture: asset,
-                transform: Transform {
-                    translation: Vec3::new(spawn_x, spawn_y, 0.),
-                    rotation: Quat::from_rotation_z(PI),
-                    scale: Vec3::new(1.,1.,1.)
-                },
-                ..default()
-            },
+            spatial_bundle: SpatialBundle::from_transform(Transform {
+                translation: Vec3::new(spawn_x, spawn_y, 0.),
+                rotation: Quat::from_rotation_z(PI),
+                scale: Vec3::new(1.,1.,1.)
+            }),
+            sprite: SpriteAsset::new(def.sprite.clone()),
             enemy: Enemy {
+                name: def.name.clone(),
                 tick: 0.,
-                t: t,
+                t: def.behavior,
                 last_shot: 0.,
-                x_path: path.0,
-                y_path: path.1,
-                shot_range: shot_range,
-                gun: gun
+                path: def.path.clone(),
+                shot_range: def.shot_delay,
+                score: (def.score, def.multiplier),
+                velocity: Vec2::ZERO,
+                contact_cooldown: 0.,
+                gun: gun::Gun::new_from_blueprint(starting_bullets, def.gun.blueprint())
 
             },
             collider: Collider,
-            health: health
+            hitbox: Hitbox::enemy(),
+            health: health::Health::new(def.health.shield, def.health.health, def.health.shield_time, def.health.recharge)
+        }
+    }
+
+    /// Drawn and hit this many times the normal size
+    pub fn scaled(mut self, scale: f32) -> EnemyBundle {
+        self.spatial_bundle.transform.scale = Vec3::new(scale, scale, 1.);
+        self
+    }
+
+    /// A boss starting in its first phase, `health_scale` multiplies its health pool (bosses get tougher every time)
+    pub fn new_boss(spawn_x: f32, spawn_y: f32, def: &BossDef, health_scale: i64) -> EnemyBundle {
+        let phase = &def.phases[0];
+
+        EnemyBundle {
+            spatial_bundle: SpatialBundle::from_transform(Transform {
+                translation: Vec3::new(spawn_x, spawn_y, 0.),
+                rotation: Quat::from_rotation_z(PI),
+                scale: Vec3::new(def.scale, def.scale, 1.)
+            }),
+            sprite: SpriteAsset::new(def.sprite.clone()),
+            enemy: Enemy {
+                name: def.name.clone(),
+                tick: 0.,
+                t: EnemyType::Boss,
+                last_shot: 0.,
+                path: phase.path.clone(),
+                shot_range: phase.shot_delay,
+                score: (def.score, def.multiplier),
+                velocity: Vec2::ZERO,
+                contact_cooldown: 0.,
+                gun: phase.gun()
+            },
+            collider: Collider,
+            hitbox: Hitbox::enemy(),
+            health: health::Health::new(def.health.shield * health_scale, def.health.health * health_scale, def.health.shield_time, def.health.recharge)
         }
     }
 
@@ -144,44 +186,59 @@ impl EnemyBundle {
 const TICK_MAX: f32 = 60. * 3.; // reset tick after 3 min  
 
 /// Control enemy movement and behavior 
+#[allow(clippy::too_many_arguments)]
 pub fn enemy_control(
     time: Res<Time>,
-    mut sprite_position: Query<(Entity, &mut Transform, &mut Enemy), With<Enemy>>,
+    mut sprite_position: Query<(Entity, &mut Transform, &mut Enemy, Has<FormationMember>), With<Enemy>>,
     mut commands: Commands,
-    mut asset_server: Res<AssetServer>,
-    mut p_timer: ResMut<PowerUpTimer>
+    mut pool: ResMut<BulletPool>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut p_timer: ResMut<PowerUpTimer>,
+    mut rng: ResMut<GameRng>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>,
+    target: Res<PlayerTarget>,
+    playfield: Res<Playfield>
 
 ) {
     p_timer.0.tick(time.delta()); // tick the power up timer always and reset after enemies die
-    for(_, mut transform, mut enemy) in &mut sprite_position{
+    for(_, mut transform, mut enemy, in_formation) in &mut sprite_position{
         enemy.tick += time.delta_seconds();
-        // Implement bounding
-        if transform.translation.y > T_BOUND as f32 { transform.translation.y -= DEFAULT_FALL_SPEED; continue;} // If the enemy is above the screen bounds we want it to drop down to the screen 
-        if transform.translation.x < 0. - L_BOUND as f32 { transform.translation.x = R_BOUND as f32 - 1.; continue; } // Check to make sure we havent moved over the bounds ( if we have pacman across to the other side and continue moving)
-        if transform.translation.x > R_BOUND as f32 { transform.translation.x = 0. - L_BOUND as f32 + 1.; continue; } 
-        if transform.translation.y < B_BOUND { 
-            transform.translation.y = T_BOUND as f32 + 50.;
-            enemy.tick = if enemy.tick > TICK_MAX { 0. } else {enemy.tick};
-            spawn_wave_box(2, &mut asset_server, &mut commands); // Consequence of letting an enemy get to the bottom
-        }
+        enemy.contact_cooldown -= time.delta_seconds();
+        if !in_formation { // formations move their members themselves
+            // Implement bounding
+            if transform.translation.y > playfield.top { transform.translation.y -= DEFAULT_FALL_SPEED; continue;} // If the enemy is above the screen bounds we want it to drop down to the screen 
+            if let Some(x) = playfield.wrap_x(transform.translation.x) { transform.translation.x = x; continue; } // Check to make sure we havent moved over the bounds ( if we have pacman across to the other side and continue moving)
+            if transform.translation.y < playfield.bottom { 
+                transform.translation.y = playfield.top + 50.;
+                enemy.tick = if enemy.tick > TICK_MAX { 0. } else {enemy.tick};
+                if let Some(defs) = defs.get(&defs_handle.0) {
+                    spawn_wave_box(2, &mut commands, &mut sfx, &mut rng, defs, &playfield); // Consequence of letting an enemy get to the bottom
+                }
+            }
 
-        transform.translation.y += (enemy.y_path)(enemy.tick) * -1. as f32; // run the y function
-        transform.translation.x += (enemy.x_path)(enemy.tick) * -1. as f32; // run the x function
+            let step = match (enemy.t, target.position) {
+                (EnemyType::Melee, Some(player)) => enemy.chase(transform.translation.truncate(), player, time.delta_seconds()),
+                _ => enemy.path.eval(enemy.tick) * -1., // run the x / y functions
+            };
+            transform.translation.y += step.y;
+            transform.translation.x += step.x;
+        }
         
         // Shot Logic (I wanna change this so they fire individually more often)
         enemy.last_shot += time.delta_seconds();
-        let random_shot_delay: f32 = rand::thread_rng().gen_range(enemy.shot_range.0 .. enemy.shot_range.1);
+        let random_shot_delay: f32 = rng.0.gen_range(enemy.shot_range.0 .. enemy.shot_range.1);
 
-        if enemy.last_shot > random_shot_delay && transform.translation.y < T_BOUND as f32{
+        if enemy.last_shot > random_shot_delay && transform.translation.y < playfield.top {
             enemy.last_shot = 0. - random_shot_delay as f32;
             let spawn_x = transform.translation.x;
             let spawn_y = transform.translation.y - 30.;
             match enemy.t {
                 EnemyType::Spawner => {
                     
-                    let rng_rad: f32 = rand::thread_rng().gen_range(100. .. 580.);
+                    let rng_rad: f32 = rng.0.gen_range(100. .. 580.);
                     let size = ((rng_rad / 100.) * 25. ) as usize;
-                    let roll = rand::thread_rng().gen_range(0..3);
+                    let roll = rng.0.gen_range(0..3);
                     let x = transform.translation.x;
                     let y = transform.translation.y;
                     let points = match roll {
@@ -201,26 +258,21 @@ pub fn enemy_control(
                     };
 
                     
-                    commands.spawn(AudioBundle {
-                        source: asset_server.load("sounds/shieldhit.wav"),
-                        // auto-despawn the entity when playback finishes
-                        settings: PlaybackSettings::DESPAWN,
-                    });
+                    sfx.send(PlaySfx(Sfx::ShieldHit));
+                    let burst = trajectory::path("0", "t*t");
                     for p in points {
-                        commands.spawn(bullet::BulletBundle::new(p.0, p.1, bullet::Bullet::new(-1, |y| y*y, |_| 0., 0., false, 50),asset_server.load("plasma_purple.png") ));
+                        pool.spawn(&mut commands, p.0, p.1, bullet::Bullet::new(-1, burst.clone(), 0., false, 50), Plasma::Purple);
                     }
                 },
                 EnemyType::Melee => {},
                 _ => {
-                    commands.spawn(AudioBundle {
-                        source: asset_server.load("sounds/laser.wav"),
-                        // auto-despawn the entity when playback finishes
-                        settings: PlaybackSettings::DESPAWN
-                    });
+                    sfx.send(PlaySfx(Sfx::Laser));
 
+                    let aim = enemy.gun.get_aim();
                     let bullets = enemy.gun.get_bullets();
                     for bul in bullets {
-                        commands.spawn(bullet::BulletBundle::new(spawn_x, spawn_y, bullet::Bullet::new(bul.0, bul.1, bul.2, bul.3, bul.4, bul.5),asset_server.load("plasma_red.png") ));
+                        let shot = aim.apply(bullet::Bullet::new(bul.0, bul.1.clone(), bul.2, bul.3, bul.4), Vec2::new(spawn_x, spawn_y), &target);
+                        pool.spawn(&mut commands, spawn_x, spawn_y, shot, Plasma::Red);
                     }
                 }
             }
@@ -233,57 +285,67 @@ pub fn enemy_control(
         
         
         if p_timer.0.finished() {
-            power_ups::spawn_powerup_wave(&mut commands, &asset_server);
+            power_ups::spawn_powerup_wave(&mut commands, &mut rng);
             p_timer.0.reset();
         }
     }
 }
 
 
-fn spawn_wave_box(wave_size: u32, asset_server: &mut Res<AssetServer>, commands: &mut Commands) {
-    commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/warp.wav"),
-        // auto-despawn the entity when playback finishes
-        settings: PlaybackSettings::DESPAWN,
-    });
+/// Enemies running into the player hurt them and reset the multiplier like a bullet would, melee enemies blow themselves up doing it
+pub fn contact_damage(
+    mut commands: Commands,
+    mut enemies: Query<(Entity, &Transform, &Hitbox, &mut Enemy)>,
+    grid: Res<SpatialGrid>,
+    mut collision_events: EventWriter<bullet::CollisionEvent>,
+    mut scoreboard: ResMut<ScoreBoard>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut explosions: EventWriter<Explode>
+){
+    for (entity, transform, hitbox, mut enemy) in &mut enemies {
+        if enemy.contact_cooldown > 0. || !hitbox.mask.contains(Layer::Player) { continue; }
+
+        let position = transform.translation.truncate();
+        let Some((player, _)) = grid.query(Layer::Player, hitbox.volume(transform)).first().copied() else { continue; };
+        scoreboard.set_mul(0);
+
+        if let EnemyType::Melee = enemy.t {
+            // blast everything around it, other enemies included (the player gets the score for those)
+            let blast = BoundingCircle::new(position, MELEE_BLAST_RADIUS);
+            for (other, _) in grid.query(Layer::Enemy, blast) {
+                if other != entity {
+                    collision_events.send(bullet::CollisionEvent(other, MELEE_BLAST_DAMAGE, false));
+                }
+            }
+            collision_events.send(bullet::CollisionEvent(player, MELEE_BLAST_DAMAGE, true));
+
+            sfx.send(PlaySfx(Sfx::Hit));
+            explosions.send(Explode(transform.translation, Color::ORANGE_RED));
+            commands.entity(entity).despawn();
+        } else {
+            collision_events.send(bullet::CollisionEvent(player, CONTACT_DAMAGE, true));
+            sfx.send(PlaySfx(Sfx::ShieldHit));
+            enemy.contact_cooldown = CONTACT_COOLDOWN;
+        }
+    }
+}
+
+
+fn spawn_wave_box(wave_size: u32, commands: &mut Commands, sfx: &mut EventWriter<PlaySfx>, rng: &mut GameRng, defs: &EnemyDefs, playfield: &Playfield) {
+    sfx.send(PlaySfx(Sfx::Warp));
+
+    let total_weight: u32 = defs.enemies.iter().map(|def| def.spawn_weight).sum();
+    if total_weight == 0 { return; }
 
     for _ in 1..wave_size { // spawns offset by 1
         
         
-        let spawn_x = rand::thread_rng().gen_range( (0. - L_BOUND as f32)..(R_BOUND as f32));
-        let spawn_y = rand::thread_rng().gen_range( (T_BOUND as f32)..(T_BOUND as f32 + 200.));
-
-        let rng = rand::thread_rng().gen_range(0..=100);
-        match rng {
-            0..=23 => {commands.spawn(EnemyBundle::new(spawn_x, spawn_y, EnemyType::Melee, asset_server.load("enemies/melee.png"), health::Health::new(20,150, 3.5,5), MELEE_PATH, LINEAR_DELAY, gun::Gun::new(Vec::new(), 0., 0, 1, 0, 0.)));},
-            24..=48 => {
-                let mut starting_bullets = Vec::new();
-                starting_bullets.push(BULLET_STRAIGHT);
-                commands.spawn(EnemyBundle::new(spawn_x, spawn_y, EnemyType::Linear, asset_server.load("enemies/basic.png"), health::Health::new(0,150, 0.0, 5), LINEAR_PATH, LINEAR_DELAY, gun::Gun::new_from_blueprint(starting_bullets, GUN_BLUEPRINT_LINEAR)));
-            },
-            49..=73 => {
-                let mut starting_bullets = Vec::new();
-                starting_bullets.push(BULLET_COS_POS);
-                starting_bullets.push(BULLET_COS_NEG);
-                commands.spawn(EnemyBundle::new(spawn_x, spawn_y, EnemyType::Wavy, asset_server.load("enemies/wavy.png"), health::Health::new(150,150, 3.0, 5), WAVY_PATH, LINEAR_DELAY, gun::Gun::new_from_blueprint(starting_bullets, GUN_BLUEPRINT_WAVY)));
-            },
-            74..=95 => {
-                let mut starting_bullets = Vec::new();
-                starting_bullets.push(BULLET_DAIG_NEG_0);
-                starting_bullets.push(BULLET_DAIG_NEG_1);
-                starting_bullets.push(BULLET_DAIG_POS_0);
-                starting_bullets.push(BULLET_DAIG_POS_1);
-                commands.spawn(EnemyBundle::new(spawn_x, spawn_y, EnemyType::Spammer, asset_server.load("enemies/spammer.png"), health::Health::new(100,150, 3.0, 5), SPAMMER_PATH, SPAMMER_DELAY, gun::Gun::new_from_blueprint(starting_bullets, GUN_BLUEPRINT_SPAMMER)));
-            },
-            96..=100 => {
-                commands.spawn(EnemyBundle::new(spawn_x, spawn_y, EnemyType::Spawner, asset_server.load("enemies/spawner.png"), health::Health::new(200,250, 3.0, 5), SPAWNER_PATH, SPAWNER_DELAY, gun::Gun::new(Vec::new(), 0., 0, 1, 1000, 2.0)));
-            },
-            _ => ()
+        let spawn_x = rng.0.gen_range(playfield.left..playfield.right);
+        let spawn_y = rng.0.gen_range(playfield.top..(playfield.top + 200.));
 
+        if let Some(def) = defs.pick(rng) {
+            commands.spawn(EnemyBundle::new(spawn_x, spawn_y, def));
         }
-
-        
-        
     }
 
 }
@@ -292,20 +354,35 @@ fn spawn_wave_box(wave_size: u32, asset_server: &mut Res<AssetServer>, commands:
 
 pub fn init_wave(
     mut commands: Commands,
-    mut asset_server: Res<AssetServer>
+    mut sfx: EventWriter<PlaySfx>,
+    mut rng: ResMut<GameRng>,
+    mode: Res<GameMode>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>,
+    playfield: Res<Playfield>
 ){
-        spawn_wave_box(WAVE_SIZE, &mut asset_server, &mut commands);
+        // the campaign brings in its own enemies
+        if *mode == GameMode::Endless {
+            if let Some(defs) = defs.get(&defs_handle.0) {
+                spawn_wave_box(WAVE_SIZE, &mut commands, &mut sfx, &mut rng, defs, &playfield);
+            }
+        }
         commands.insert_resource(WaveTimer(Timer::new(Duration::from_secs_f32(WAVE_INTERVAL), TimerMode::Repeating)));
         commands.insert_resource(PowerUpTimer(Timer::new(Duration::from_secs_f32(8.), TimerMode::Once)));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn wave_manager(
     mut commands: Commands,
     mut timer: ResMut<WaveTimer>,
     time: Res<Time>,
     game_time: Res<GameTimer>,
-    mut asset_server: Res<AssetServer>,
-    mut score_board: ResMut<ScoreBoard>
+    mut sfx: EventWriter<PlaySfx>,
+    mut score_board: ResMut<ScoreBoard>,
+    mut rng: ResMut<GameRng>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>,
+    playfield: Res<Playfield>
 )
 {
     timer.0.tick(time.delta());
@@ -317,7 +394,9 @@ pub fn wave_manager(
         timer.0.set_duration(Duration::from_secs_f32(dur)); // update the wave timer to be smaller
 
         let size = WAVE_SIZE * (minutes_elapsed + 1.) as u32; // wave size * minutes elapsed
-        spawn_wave_box(size, &mut asset_server, &mut commands);
+        if let Some(defs) = defs.get(&defs_handle.0) {
+            spawn_wave_box(size, &mut commands, &mut sfx, &mut rng, defs, &playfield);
+        }
 
         timer.0.reset();
 
@@ -0,0 +1,328 @@
+use std::cmp::Ordering;
+
+use bevy::{asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState}, prelude::*, utils::BoxedFuture};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{aim::Aim, campaign::{self, Campaign, CampaignHandle, CampaignLoader}, enemy::EnemyType, game::GameRng, gun, trajectory::Trajectory, GameState};
+
+const ENEMY_DEFS_PATH: &str = "enemies/archetypes.enemies.ron";
+
+/// Every enemy archetype the waves can spawn, loaded from `assets/enemies/archetypes.enemies.ron`
+#[derive(Asset, TypePath, Deserialize)]
+pub struct EnemyDefs {
+    pub enemies: Vec<EnemyDef>,
+    /// Bosses take turns showing up at milestone waves
+    #[serde(default)]
+    pub bosses: Vec<BossDef>,
+}
+
+impl EnemyDefs {
+    /// Look an enemy up by the name it has in the definition file
+    pub fn get(&self, name: &str) -> Option<&EnemyDef> {
+        self.enemies.iter().find(|def| def.name == name)
+    }
+
+    /// Pick an enemy with a chance proportional to its spawn weight
+    pub fn pick(&self, rng: &mut GameRng) -> Option<&EnemyDef> {
+        let total_weight: u32 = self.enemies.iter().map(|def| def.spawn_weight).sum();
+        if total_weight == 0 { return None; }
+
+        let mut roll = rng.0.gen_range(0..total_weight);
+        for def in &self.enemies {
+            if roll < def.spawn_weight {
+                return Some(def);
+            }
+            roll -= def.spawn_weight;
+        }
+        None
+    }
+
+    /// Catch values the game can't run with, naming the enemy they belong to
+    fn validate(&self) -> Result<(), String> {
+        for def in &self.enemies {
+            check_health(&def.name, &def.health)?;
+            check_shot_delay(&def.name, def.shot_delay)?;
+        }
+        for boss in &self.bosses {
+            check_health(&boss.name, &boss.health)?;
+            if boss.phases.is_empty() {
+                return Err(format!("boss {} has no phases", boss.name));
+            }
+            if boss.scale <= 0. || boss.scale.is_nan() {
+                return Err(format!("boss {} has scale {}, it has to be above 0", boss.name, boss.scale));
+            }
+            for phase in &boss.phases {
+                check_shot_delay(&boss.name, phase.shot_delay)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_health(name: &str, health: &HealthDef) -> Result<(), String> {
+    if health.health <= 0 {
+        return Err(format!("{} has health {}, it has to be above 0", name, health.health));
+    }
+    Ok(())
+}
+
+fn check_shot_delay(name: &str, (min, max): (f32, f32)) -> Result<(), String> {
+    // the delay is rolled from min..max, which needs min below max
+    if !matches!(min.partial_cmp(&max), Some(Ordering::Less)) {
+        return Err(format!("{} has shot_delay ({}, {}), the first number has to be smaller than the second", name, min, max));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EnemyDef {
+    pub name: String,
+    /// Which hardcoded behavior (shooting / bursts) the enemy uses
+    pub behavior: EnemyType,
+    pub sprite: String,
+    pub health: HealthDef,
+    /// How far the enemy moves down (y) and left (x) each tick, as expressions of its lifetime `t`
+    pub path: Trajectory,
+    /// Random delay range between shots in seconds
+    pub shot_delay: (f32, f32),
+    pub gun: GunDef,
+    pub bullets: Vec<BulletDef>,
+    pub score: u64,
+    pub multiplier: u64,
+    /// Relative chance of this enemy being picked for a wave (0 never spawns it in waves)
+    pub spawn_weight: u32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct HealthDef {
+    pub shield: i64,
+    pub health: i64,
+    pub shield_time: f32,
+    pub recharge: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct GunDef {
+    pub shoot_delay: f32,
+    pub damage: i64,
+    pub max_bullets: u8,
+    pub max_ammo: u64,
+    pub reload_delay: f32,
+    /// Where the shots are pointed, straight down when left out
+    #[serde(default)]
+    pub aim: Aim,
+}
+
+impl GunDef {
+    pub fn blueprint(&self) -> gun::GunBluePrint {
+        gun::GunBluePrint(self.shoot_delay, self.damage, self.max_bullets, self.max_ammo, self.reload_delay, self.aim)
+    }
+}
+
+/// A bullet the enemy's gun fires (downwards, or turned towards the player by the gun's aim)
+#[derive(Deserialize, Clone)]
+pub struct BulletDef {
+    pub path: Trajectory,
+    pub damage: i64,
+}
+
+impl BulletDef {
+    pub fn blueprint(&self) -> gun::BulletBlueprint {
+        gun::BulletBlueprint(-1, self.path.clone(), 0., false, self.damage)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BossDef {
+    pub name: String,
+    pub sprite: String,
+    /// How many times bigger than a normal enemy it is (drawn and hit)
+    pub scale: f32,
+    pub health: HealthDef,
+    pub score: u64,
+    pub multiplier: u64,
+    /// Phases in order, the health pool is split evenly between them
+    pub phases: Vec<BossPhaseDef>,
+}
+
+/// How a boss moves and shoots until it loses its share of health for this phase
+#[derive(Deserialize, Clone)]
+pub struct BossPhaseDef {
+    pub path: Trajectory,
+    pub shot_delay: (f32, f32),
+    pub gun: GunDef,
+    pub bullets: Vec<BulletDef>,
+    #[serde(default)]
+    pub burst: Option<BurstDef>,
+}
+
+impl BossPhaseDef {
+    pub fn gun(&self) -> gun::Gun {
+        gun::Gun::new_from_blueprint(self.bullets.iter().map(|b| b.blueprint()).collect(), self.gun.blueprint())
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum BurstShape {
+    Circle,
+    Square,
+    Triangle,
+}
+
+/// A ring (or square / triangle) of bullets fired around the boss every `delay` seconds
+#[derive(Deserialize, Clone)]
+pub struct BurstDef {
+    pub shape: BurstShape,
+    pub size: f32,
+    pub bullets: usize,
+    pub delay: f32,
+    pub path: Trajectory,
+    pub damage: i64,
+}
+
+#[derive(Default)]
+pub struct EnemyDefsLoader;
+
+impl AssetLoader for EnemyDefsLoader {
+    type Asset = EnemyDefs;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<EnemyDefs, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let defs = ron::de::from_bytes::<EnemyDefs>(&bytes)?;
+            defs.validate()?;
+            Ok(defs)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemies.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct EnemyDefsHandle(pub Handle<EnemyDefs>);
+
+/// Load enemy definitions (and the campaign) and hold the game in `GameState::Loading` until they are ready
+pub struct EnemyDefsPlugin {
+    /// State to go to once everything is loaded
+    pub next_state: GameState,
+}
+
+#[derive(Resource)]
+struct AfterLoading(GameState);
+
+impl Plugin for EnemyDefsPlugin {
+    fn build(&self, app: &mut App){
+        app
+            .init_asset::<EnemyDefs>()
+            .init_asset_loader::<EnemyDefsLoader>()
+            .init_asset::<Campaign>()
+            .init_asset_loader::<CampaignLoader>()
+            .insert_resource(AfterLoading(self.next_state))
+            .add_systems(Startup, load_enemy_defs)
+            .add_systems(Update, (
+                finish_loading.run_if(in_state(GameState::Loading)),
+                reload_enemy_defs
+            ));
+    }
+}
+
+fn load_enemy_defs(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands.insert_resource(EnemyDefsHandle(asset_server.load(ENEMY_DEFS_PATH)));
+    commands.insert_resource(CampaignHandle(asset_server.load(campaign::CAMPAIGN_PATH)));
+}
+
+fn finish_loading(
+    asset_server: Res<AssetServer>,
+    handle: Res<EnemyDefsHandle>,
+    campaign_handle: Res<CampaignHandle>,
+    after: Res<AfterLoading>,
+    mut game_state: ResMut<NextState<GameState>>
+){
+    match asset_server.get_load_state(&handle.0) {
+        Some(LoadState::Loaded) => match asset_server.get_load_state(&campaign_handle.0) {
+            Some(LoadState::Loaded) => game_state.set(after.0),
+            // endless mode can do without the campaign
+            Some(LoadState::Failed) => {
+                warn!("could not load {}, campaign runs will go straight back to the menu", campaign::CAMPAIGN_PATH);
+                game_state.set(after.0);
+            },
+            _ => ()
+        },
+        Some(LoadState::Failed) => {
+            error!("could not load {}, there is nothing to fight", ENEMY_DEFS_PATH);
+            std::process::exit(1); // not a clean exit, so scripts running headless see it failed
+        },
+        _ => ()
+    }
+}
+
+/// F5 reloads the definitions by hand, with the `hot_reload` feature they also reload when the file is saved
+fn reload_enemy_defs(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<AssetEvent<EnemyDefs>>
+){
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        asset_server.reload(ENEMY_DEFS_PATH);
+        asset_server.reload(campaign::CAMPAIGN_PATH);
+    }
+
+    for event in events.read() {
+        if let AssetEvent::Modified { .. } = event {
+            info!("reloaded enemy definitions, new spawns will use them");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_defs_are_valid() {
+        let defs: EnemyDefs = ron::from_str(include_str!("../assets/enemies/archetypes.enemies.ron")).unwrap();
+        assert_eq!(defs.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_empty_or_backwards_shot_delays() {
+        let mut defs: EnemyDefs = ron::from_str(include_str!("../assets/enemies/archetypes.enemies.ron")).unwrap();
+        let name = defs.enemies[0].name.clone();
+
+        for delay in [(1., 1.), (2., 1.), (f32::NAN, 1.)] {
+            defs.enemies[0].shot_delay = delay;
+            let error = defs.validate().unwrap_err();
+            assert!(error.contains(&name), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_health_and_bosses() {
+        let defs = || ron::from_str::<EnemyDefs>(include_str!("../assets/enemies/archetypes.enemies.ron")).unwrap();
+        let breakages: [fn(&mut EnemyDefs); 4] = [
+            |defs| defs.enemies[0].health.health = 0,
+            |defs| defs.bosses[0].phases.clear(),
+            |defs| defs.bosses[0].health.health = -5,
+            |defs| defs.bosses[0].scale = 0.,
+        ];
+
+        for (i, breakage) in breakages.iter().enumerate() {
+            let mut defs = defs();
+            let names = [defs.enemies[0].name.clone(), defs.bosses[0].name.clone()];
+            breakage(&mut defs);
+            let error = defs.validate().expect_err(&format!("breakage {} should be rejected", i));
+            assert!(names.iter().any(|name| error.contains(name.as_str())), "{}", error);
+        }
+    }
+}
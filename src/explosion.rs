@@ -1,6 +1,10 @@
 use bevy_hanabi::prelude::*;
 use bevy::prelude::*;
 
+/// Spawn an explosion effect at a position with a color (sent by the simulation, drawn by the renderer)
+#[derive(Event)]
+pub struct Explode(pub Vec3, pub Color);
+
 /// Stolen setup from github examples https://github.com/rust-adventure/asteroids/blob/main/src/meteors.rs
 pub fn setup(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
     
@@ -65,4 +69,34 @@ pub fn setup(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
             EffectProperties::default(),
         ))
         .insert(Name::new("effect:meteor_explosion"));
-}
\ No newline at end of file
+}
+
+/// Move the explosion effect to where something blew up and fire it
+pub fn spawn_explosions(
+    mut explosions: EventReader<Explode>,
+    mut effect: Query<(
+        &mut EffectProperties,
+        &mut EffectSpawner,
+        &mut Transform,
+    )>,
+){
+    for explosion in explosions.read() {
+        let Ok((
+            mut properties,
+            mut spawner,
+            mut effect_transform,
+        )) = effect.get_single_mut()
+        else {
+            warn!("effect not ready yet, returning");
+            return;
+        };
+
+        properties.set(
+            "spawn_color",
+            explosion.1.as_linear_rgba_u32().into(),
+        );
+
+        effect_transform.translation = explosion.0;
+        spawner.reset(); // spawn the effect 
+    }
+}
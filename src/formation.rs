@@ -0,0 +1,236 @@
+use std::{collections::VecDeque, f32::consts::PI, time::Duration};
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    enemy::{Enemy, EnemyBundle}, enemy_defs::{EnemyDef, EnemyDefs, EnemyDefsHandle}, game::{GameRng, ScoreBoard},
+    playfield::Playfield, sfx::{PlaySfx, Sfx}, trajectory::{self, Trajectory}
+};
+
+/// How fast members close in on their place in the formation (fraction of the gap per tick)
+const CATCH_UP: f32 = 0.15;
+/// Endless waves bring in a formation every wave from this one on
+const FORMATION_FROM_WAVE: u64 = 2;
+
+/// How a group is laid out around its anchor
+#[derive(Deserialize, Clone, Default)]
+pub enum Formation {
+    /// Scattered above the screen like the endless waves (doesn't keep together)
+    #[default]
+    Random,
+    /// Side by side, centered on the anchor
+    Row(f32),
+    /// One behind the other, going up from the anchor
+    Column(f32),
+    /// Leader at the anchor, the rest trailing up and out to both sides
+    V(f32),
+    /// Rows of this many columns going up from the anchor, spaced apart by the second value
+    Grid(usize, f32),
+    /// Evenly around the anchor at this radius
+    Circle(f32),
+    /// Following the anchor's trail, each member this many ticks behind the one ahead (a column when it isn't moving)
+    Snake(f32),
+    /// Offsets from the anchor, reused from the start if there are more enemies than points
+    At(Vec<(f32, f32)>),
+}
+
+impl Formation {
+    /// Where member `i` of `count` sits relative to the anchor
+    fn offset(&self, i: usize, count: usize) -> Vec2 {
+        match self {
+            Formation::Random => Vec2::ZERO,
+            Formation::Row(spacing) => Vec2::new((i as f32 - (count - 1) as f32 / 2.) * spacing, 0.),
+            Formation::Column(spacing) => Vec2::new(0., i as f32 * spacing),
+            Formation::V(spacing) => {
+                let rank = i.div_ceil(2) as f32;
+                let side = if i % 2 == 0 { 1. } else { -1. };
+                Vec2::new(side * rank * spacing, rank * spacing / 2.)
+            },
+            Formation::Grid(columns, spacing) => {
+                let columns = (*columns).max(1);
+                let in_row = columns.min(count - (i / columns) * columns);
+                Vec2::new(((i % columns) as f32 - (in_row - 1) as f32 / 2.) * spacing, (i / columns) as f32 * spacing)
+            },
+            Formation::Circle(radius) => {
+                let angle = i as f32 / count as f32 * PI * 2.;
+                Vec2::new(angle.cos(), angle.sin()) * *radius
+            },
+            Formation::Snake(ticks) => Vec2::new(0., i as f32 * ticks * 4.),
+            Formation::At(points) if !points.is_empty() => Vec2::from(points[i % points.len()]),
+            Formation::At(_) => Vec2::ZERO,
+        }
+    }
+
+    /// Where member `i` of `count` spawns for a group placed at `anchor`
+    pub fn position(&self, i: usize, count: usize, anchor: Vec2, rng: &mut GameRng, playfield: &Playfield) -> Vec2 {
+        match self {
+            Formation::Random => Vec2::new(
+                rng.0.gen_range(playfield.left..playfield.right),
+                rng.0.gen_range(playfield.top..(playfield.top + 200.))
+            ),
+            _ => anchor + self.offset(i, count),
+        }
+    }
+}
+
+/// Makes a formation move as one: the anchor follows `path` and every so often a member breaks off to dive
+#[derive(Deserialize, Clone)]
+pub struct GroupDef {
+    pub path: Trajectory,
+    /// Seconds between members breaking off (0 keeps them all in formation)
+    #[serde(default)]
+    pub dive_every: f32,
+    /// Path a member flies once it breaks off
+    #[serde(default = "default_dive_path")]
+    pub dive_path: Trajectory,
+}
+
+fn default_dive_path() -> Trajectory {
+    trajectory::path("0", "t*t / 10 + 2")
+}
+
+/// Anchor of a group of enemies flying in formation, members keep to their places around it as it moves
+#[derive(Component)]
+pub struct FormationGroup {
+    formation: Formation,
+    path: Trajectory,
+    tick: f32,
+    /// Members in formation order, dead ones are dropped and the rest close ranks
+    members: Vec<Entity>,
+    dive_timer: Option<Timer>,
+    dive_path: Trajectory,
+    /// Where the anchor has been, newest first (for snakes)
+    trail: VecDeque<Vec2>,
+}
+
+/// Enemy that is flown by its formation instead of its own path
+#[derive(Component)]
+pub struct FormationMember;
+
+/// Spawn `count` enemies in formation around `anchor`, moving together as `group` says
+pub fn spawn_group(commands: &mut Commands, def: &EnemyDef, count: usize, formation: &Formation, anchor: Vec2, group: &GroupDef) {
+    let members = (0..count).map(|i| {
+        let position = anchor + formation.offset(i, count);
+        commands.spawn((EnemyBundle::new(position.x, position.y, def), FormationMember)).id()
+    }).collect();
+
+    commands.spawn((
+        Transform::from_translation(anchor.extend(0.)),
+        FormationGroup {
+            formation: formation.clone(),
+            path: group.path.clone(),
+            tick: 0.,
+            members,
+            dive_timer: (group.dive_every > 0.).then(|| Timer::new(Duration::from_secs_f32(group.dive_every), TimerMode::Repeating)),
+            dive_path: group.dive_path.clone(),
+            trail: VecDeque::new(),
+        }
+    ));
+}
+
+/// Move formations along their paths, keep members in place, send divers off and clear out empty formations
+pub fn move_formations(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    mut groups: Query<(Entity, &mut FormationGroup, &mut Transform), Without<Enemy>>,
+    mut members: Query<(&mut Transform, &mut Enemy), With<FormationMember>>,
+    playfield: Res<Playfield>
+){
+    for (entity, mut group, mut anchor) in &mut groups {
+        group.members.retain(|member| members.contains(*member));
+        if group.members.is_empty() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        group.tick += time.delta_seconds();
+        let step = group.path.eval(group.tick) * -1.;
+        anchor.translation += step.extend(0.);
+
+        // wrap around like single enemies do, members jump with it
+        let mut snap = false;
+        if let Some(x) = playfield.wrap_x(anchor.translation.x) { anchor.translation.x = x; snap = true; }
+        if anchor.translation.y < playfield.bottom { anchor.translation.y = playfield.top + 50.; snap = true; }
+
+        let position = anchor.translation.truncate();
+        if snap { group.trail.clear(); }
+        group.trail.push_front(position);
+
+        if let Formation::Snake(ticks) = group.formation {
+            let keep = (group.members.len() as f32 * ticks.max(1.)) as usize + 1;
+            group.trail.truncate(keep);
+        } else {
+            group.trail.truncate(1);
+        }
+
+        let count = group.members.len();
+        for (i, member) in group.members.iter().enumerate() {
+            let Ok((mut transform, _)) = members.get_mut(*member) else { continue; };
+
+            let target = match group.formation {
+                Formation::Snake(ticks) => group.trail.get((i as f32 * ticks.max(1.)) as usize).copied()
+                    .unwrap_or(position + group.formation.offset(i, count)),
+                _ => position + group.formation.offset(i, count),
+            };
+            let current = transform.translation.truncate();
+            let next = if snap { target } else { current + (target - current) * CATCH_UP };
+            transform.translation.x = next.x;
+            transform.translation.y = next.y;
+        }
+
+        let Some(dive_timer) = group.dive_timer.as_mut() else { continue; };
+        dive_timer.tick(time.delta());
+        // only dive once the formation is on screen
+        if !dive_timer.just_finished() || position.y > playfield.top { continue; }
+
+        let pick = rng.0.gen_range(0..group.members.len());
+        let diver = group.members.remove(pick);
+        if let Ok((_, mut enemy)) = members.get_mut(diver) {
+            enemy.set_path(group.dive_path.clone());
+            commands.entity(diver).remove::<FormationMember>();
+        }
+    }
+}
+
+/// Endless waves bring in a formation alongside the usual scattered enemies once things get going
+#[allow(clippy::too_many_arguments)]
+pub fn formation_waves(
+    mut commands: Commands,
+    mut last_wave: Local<u64>,
+    score_board: Res<ScoreBoard>,
+    mut rng: ResMut<GameRng>,
+    mut sfx: EventWriter<PlaySfx>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>,
+    playfield: Res<Playfield>
+){
+    let wave = score_board.get_waves();
+    if wave == *last_wave { return; }
+    *last_wave = wave; // also catches the count going back to 0 on a new run
+    if wave < FORMATION_FROM_WAVE { return; }
+
+    let Some(defs) = defs.get(&defs_handle.0) else { return; };
+    let Some(def) = defs.pick(&mut rng) else { return; };
+
+    let formation = match rng.0.gen_range(0..5) {
+        0 => Formation::V(70.),
+        1 => Formation::Row(110.),
+        2 => Formation::Grid(3, 90.),
+        3 => Formation::Circle(120.),
+        _ => Formation::Snake(10.),
+    };
+    let path = match rng.0.gen_range(0..3) {
+        0 => trajectory::path("2*sin(t/2)", "0.3"),
+        1 => trajectory::path("3*cos(t)", "0.5"),
+        _ => trajectory::path("0", "0.8"),
+    };
+    let count = rng.0.gen_range(4..=8);
+    let x = rng.0.gen_range((playfield.left / 2.)..(playfield.right / 2.));
+
+    sfx.send(PlaySfx(Sfx::Warp));
+    let group = GroupDef { path, dive_every: 4., dive_path: default_dive_path() };
+    spawn_group(&mut commands, def, count, &formation, Vec2::new(x, playfield.top + 100.), &group);
+}
@@ -1,21 +1,95 @@
-use bevy::{core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping}, prelude::*, time::Stopwatch};
+use bevy::{prelude::*, time::Stopwatch};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-use crate::{bullet, enemy, explosion, health, player, power_ups};
+use crate::{aim::{self, PlayerTarget}, bomb, boss::{self, BossTracker}, bullet, campaign::{self, CampaignRun}, bullet_pool::BulletPool, enemy, explosion, formation, graze, health, lives::{self, Lives}, player, playfield::Playfield, power_ups, sfx, spatial, spawner};
 use super::GameState;
 
 
-pub struct BulletHellElite;
+pub struct BulletHellElite {
+    /// Seed given on the command line (random every run when not given)
+    pub seed: Option<u64>,
+    /// Mode the first run is played in (the menu can change it)
+    pub mode: GameMode,
+}
+
+/// Endless survival with waves that keep growing, or the scripted levels of the campaign
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    #[default]
+    Endless,
+    Campaign,
+}
+
+/// How hard runs are: how many lives the player starts with and how much getting hit hurts
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    /// Damage the player takes is scaled by this
+    pub fn damage_taken(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    pub fn starting_lives(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 5,
+            Difficulty::Normal => 3,
+            Difficulty::Hard => 2,
+        }
+    }
+}
+
+pub fn endless_mode(mode: Res<GameMode>) -> bool {
+    *mode == GameMode::Endless
+}
+
+pub fn campaign_mode(mode: Res<GameMode>) -> bool {
+    *mode == GameMode::Campaign
+}
 
 #[derive(Resource)]
 pub struct GameTimer(pub Stopwatch);
 
+/// Seed for the next (or last) run, pinned seeds are kept between runs instead of being rerolled
 #[derive(Resource)]
-pub struct ScoreBoard { score: u64, mul: u64, waves: u64, game_time: f32 }
+pub struct RunSeed { pub seed: u64, pub pinned: bool }
 
-#[derive(Component)]
-pub struct MovingBackground(f32);
+/// The only source of randomness the simulation is allowed to use, reseeded from `RunSeed` every run
+#[derive(Resource)]
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> GameRng {
+        GameRng(StdRng::seed_from_u64(seed))
+    }
+}
 
-const SCROLL_SPEED: f32 = 0.25;
+#[derive(Resource)]
+pub struct ScoreBoard { score: u64, mul: u64, waves: u64, grazes: u64, game_time: f32 }
+
+impl Default for ScoreBoard {
+    fn default() -> Self {
+        ScoreBoard {score: 0, mul: 1, waves:0, grazes: 0, game_time: 0.0}
+    }
+}
 
 impl ScoreBoard {
 
@@ -43,6 +117,12 @@ impl ScoreBoard {
         self.waves = self.waves + 1;
     }
 
+    pub fn get_grazes(&self) -> u64 {self.grazes}
+
+    pub fn add_graze(&mut self){
+        self.grazes += 1;
+    }
+
     pub fn get_game_time(&self) -> f32 {self.game_time}
 
     pub fn set_game_time(&mut self, gt: f32) {self.game_time = gt;}
@@ -53,59 +133,62 @@ impl Plugin for BulletHellElite {
         app
             .add_event::<bullet::CollisionEvent>()
             .add_event::<bullet::ScoreEvent>()
-            .insert_resource(ScoreBoard {score: 0, mul: 1, waves:0, game_time: 0.0})
-            .add_systems(OnEnter(GameState::Game),(setup, player::spawn_player, enemy::init_wave, explosion::setup).before(player::sprite_movement))
-            .add_systems(FixedPreUpdate, advance_game_timer.run_if(in_state(GameState::Game)))
+            .add_event::<sfx::PlaySfx>()
+            .add_event::<explosion::Explode>()
+            .add_event::<bomb::BombBlast>()
+            .add_event::<lives::PlayerDown>()
+            .add_event::<lives::Respawn>()
+            .init_resource::<ScoreBoard>()
+            .init_resource::<spatial::SpatialGrid>()
+            .init_resource::<BulletPool>()
+            .init_resource::<BossTracker>()
+            .init_resource::<CampaignRun>()
+            .init_resource::<PlayerTarget>()
+            .init_resource::<Lives>()
+            .init_resource::<Playfield>()
+            .init_resource::<power_ups::Inventory>()
+            .insert_resource(self.mode)
+            .init_resource::<Difficulty>()
+            .insert_resource(RunSeed { seed: self.seed.unwrap_or(0), pinned: self.seed.is_some() })
+            .insert_resource(GameRng::from_seed(0))
+            .add_systems(OnEnter(GameState::Game),(setup, aim::reset_target, lives::reset_lives, power_ups::reset_inventory, player::spawn_player, enemy::init_wave).chain())
+            .add_systems(FixedPreUpdate, advance_game_timer.run_if(run_active))
             .add_systems(FixedUpdate, (
-                player::sprite_movement, 
-                bullet::bullet_movement, 
-                bullet::apply_collision_damage, 
-                bullet::update_score, 
+                (player::sprite_movement, bomb::use_bomb, bomb::detonate_bombs, bomb::tick_invulnerability).chain(),
+                aim::track_player,
+                spatial::rebuild_spatial_grid,
+                (bullet::bullet_bounds, bullet::bullet_movement, graze::graze).chain(),
+                (bullet::apply_collision_damage, lives::lose_life, lives::clear_spawn_area, lives::game_over).chain(),
+                (bullet::update_score, lives::award_extra_lives).chain(),
                 bullet::bullet_on_bullet_collision,
-                enemy::enemy_control, 
-                enemy::wave_manager,
-                player::update_player_score, 
-                player::update_time_display,
-                player::update_health_display,
-                player::update_ammo_display,
+                (enemy::enemy_control, spawner::adopt_spawners, spawner::spawn_minions, formation::move_formations, enemy::contact_damage).chain(),
+                enemy::wave_manager.run_if(endless_mode),
+                formation::formation_waves.run_if(endless_mode),
+                boss::boss_spawner.run_if(endless_mode),
+                campaign::load_level.run_if(campaign_mode),
+                campaign::campaign_spawner.run_if(campaign_mode),
+                campaign::campaign_progress.run_if(campaign_mode),
+                boss::boss_defeated,
+                boss::boss_control,
                 health::shield_tick, 
                 power_ups::move_powerups,
                 power_ups::handle_powerup_collision,
-                move_background_image
-                ).run_if(in_state(GameState::Game)))
+                ).chain().run_if(run_active)) // chained so every run steps in the same order
+
             .add_systems(OnExit(GameState::Game), cleanup);
     }
 }
 
 /// Setup our game world 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    
-
-    commands.spawn(( //Camera with bloom settings enabled
-        Camera2dBundle {
-            camera: Camera {
-                hdr: true,
-                ..default()
-            },
-            tonemapping: Tonemapping::TonyMcMapface,
-            ..default()
-        },
-        BloomSettings::default(),
-    ));
-
-    commands.spawn(
-        (
-            SpriteBundle {
-            texture: asset_server.load("bckg.png"),
-            transform: Transform {
-                translation: Vec3::new(0.,0., -1.0),
-                scale: Vec3::new(0.6, 0.6, 1.0),
-                ..default()
-            },
-            ..default()
-            },
-            MovingBackground(1.0)
-        ));
+pub fn setup(mut commands: Commands, mut run_seed: ResMut<RunSeed>, mut rng: ResMut<GameRng>, mut score_board: ResMut<ScoreBoard>, mut pool: ResMut<BulletPool>, mut boss_tracker: ResMut<BossTracker>, mut campaign_run: ResMut<CampaignRun>) {
+    if !run_seed.pinned {
+        run_seed.seed = rand::random();
+    }
+    *rng = GameRng::from_seed(run_seed.seed);
+    *score_board = ScoreBoard::default(); // every run starts from nothing so it can be replayed
+    *pool = BulletPool::default(); // pooled bullets were despawned with everything else when the last run ended
+    *boss_tracker = BossTracker::default();
+    *campaign_run = CampaignRun::default();
 
     commands.insert_resource(GameTimer(Stopwatch::new()));
 
@@ -126,14 +209,9 @@ fn cleanup(
     }
 }
 
-pub fn move_background_image(
-    mut bckg: Query<(&mut Transform, &mut MovingBackground), With<MovingBackground>>
-){
-    if let Ok((mut transform, mut back)) = bckg.get_single_mut() {
-        if transform.translation.y < -400. || transform.translation.y > 300. {back.0 = back.0 * -1.0}
-        transform.translation.y += SCROLL_SPEED * back.0;
-        
-    }
+/// In game and the run hasn't ended this frame (more fixed ticks can run before the state actually changes)
+pub fn run_active(state: Res<State<GameState>>, next_state: Res<NextState<GameState>>) -> bool {
+    *state.get() == GameState::Game && next_state.0.is_none()
 }
 
 pub fn advance_game_timer(time: Res<Time>, mut game_timer: ResMut<GameTimer>){
@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+
+use crate::{campaign::CampaignRun, controls::{Action, ActionState}, game::{GameMode, RunSeed, ScoreBoard}, menu};
+
+use super::GameState;
+
+/// Root of the game over screen (and its camera, the game's went with the run)
+#[derive(Component)]
+struct GameOverView;
+
+#[derive(Component)]
+struct ContinueButton;
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+pub fn game_over_plugin(app: &mut App) {
+    app
+        .add_systems(OnEnter(GameState::GameOver), spawn_screen)
+        .add_systems(Update, (button_system, continue_keys).run_if(in_state(GameState::GameOver)))
+        .add_systems(OnExit(GameState::GameOver), despawn_screen);
+}
+
+/// Enter (or pause on a gamepad) goes on to the menu too
+fn continue_keys(keyboard_input: Res<ButtonInput<KeyCode>>, actions: Res<ActionState>, mut game_state: ResMut<NextState<GameState>>){
+    if keyboard_input.just_pressed(KeyCode::Enter) || actions.just_pressed(Action::Pause) {
+        game_state.set(GameState::Menu);
+    }
+}
+
+fn button_system(
+    mut interaction_query: Query<(&Interaction, &ContinueButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut game_state: ResMut<NextState<GameState>>
+){
+    for (interaction, _, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                game_state.set(GameState::Menu); // the menu asks for a name if the run made the leaderboard
+            }
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+}
+
+fn spawn_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    score: Res<ScoreBoard>,
+    run_seed: Res<RunSeed>,
+    mode: Res<GameMode>,
+    campaign: Res<CampaignRun>
+){
+    let font = asset_server.load("fonts/EvilEmpire.otf");
+
+    commands.spawn((Camera2dBundle::default(), GameOverView));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(30.0),
+                    ..default()
+                },
+                ..default()
+            },
+            GameOverView
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Game Over",
+                TextStyle { font: font.clone(), font_size: 120.0, color: Color::RED },
+            ));
+
+            parent.spawn(TextBundle::from_section(
+                menu::last_run_text(&score, &run_seed, *mode, &campaign),
+                TextStyle { font: font.clone(), font_size: 50.0, color: Color::GOLD },
+            ).with_text_justify(JustifyText::Center));
+
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(320.0),
+                            height: Val::Px(80.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    },
+                    ContinueButton
+                ))
+                .with_children(|button_parent| {
+                    button_parent.spawn(TextBundle::from_section(
+                        "Continue",
+                        TextStyle { font: font.clone(), font_size: 48.0, color: Color::rgb(0.9, 0.9, 0.9) },
+                    ));
+                });
+        });
+}
+
+fn despawn_screen(mut commands: Commands, query: Query<Entity, With<GameOverView>>){
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,50 @@
+use bevy::{math::bounding::BoundingCircle, prelude::*};
+
+use crate::{
+    bomb::Invulnerable, bullet::{Bullet, ScoreEvent}, game::ScoreBoard, player::PlayerControlled, shapes::ShapeGun, spatial::{Layer, SpatialGrid}
+};
+
+/// Enemy bullets passing this close to the middle of the player count as a graze
+const GRAZE_RADIUS: f32 = 36.;
+/// Score for each graze (multiplied like any other score)
+const GRAZE_SCORE: u64 = 10;
+/// Grazes it takes to earn back a special (shape) shot
+const GRAZES_PER_SHAPE: u64 = 25;
+
+/// Reward enemy bullets that come close to the player and get past without hitting them, each bullet counts once (nothing counts while invulnerable)
+pub fn graze(
+    mut player: Query<(&Transform, &mut ShapeGun, Has<Invulnerable>), With<PlayerControlled>>,
+    mut bullets: Query<&mut Bullet>,
+    grid: Res<SpatialGrid>,
+    mut near: Local<Vec<Entity>>,
+    mut score_events: EventWriter<ScoreEvent>,
+    mut scoreboard: ResMut<ScoreBoard>
+){
+    let Ok((transform, mut shape_gun, invulnerable)) = player.get_single_mut() else { near.clear(); return; };
+    if invulnerable {
+        near.clear();
+        return;
+    }
+
+    // bullets that hit the player this tick went back to the pool instead of into the grid
+    let area = BoundingCircle::new(transform.translation.truncate(), GRAZE_RADIUS);
+    let inside: Vec<Entity> = grid.query(Layer::EnemyBullet, area).into_iter().map(|(entity, _)| entity).collect();
+    for entity in &inside {
+        if let Ok(mut bullet) = bullets.get_mut(*entity) {
+            bullet.pass_near();
+        }
+    }
+
+    // the ones that were close last tick and are gone now got past, unless they hit (and went back to the pool)
+    for entity in near.iter().filter(|entity| !inside.contains(entity)) {
+        let Ok(mut bullet) = bullets.get_mut(*entity) else { continue; };
+        if !bullet.is_active() || !bullet.graze() { continue; }
+
+        score_events.send(ScoreEvent(GRAZE_SCORE, 0));
+        scoreboard.add_graze();
+        if scoreboard.get_grazes() % GRAZES_PER_SHAPE == 0 {
+            shape_gun.add_shot();
+        }
+    }
+    *near = inside;
+}
@@ -2,6 +2,8 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 
+use crate::{aim::Aim, trajectory::Trajectory};
+
 
 #[derive(Resource)]
 /// Timer for shots (does not apply to AI as they have a range used by their own logic)
@@ -9,11 +11,12 @@ pub struct ShotTimer(Timer);
 
 
 // Implement a bullet blueprint allowing for quick instantiation of bullet objects without taking space on the heap until needed
-/// Bullet blueprint for constant storage of certain types:  (**dir**:*i8*, **fy**:*fn(f32)->f32*, **fx**:*fn(f32)->f32*, **tick**:*f32*, **team**:*bool*, **damage**:*i64*)
-pub struct BulletBlueprint(pub i8, pub fn(f32)->f32, pub fn(f32)->f32, pub f32, pub bool, pub i64);
+/// Bullet blueprint for constant storage of certain types:  (**dir**:*i8*, **path**:*Trajectory*, **tick**:*f32*, **team**:*bool*, **damage**:*i64*)
+#[derive(Clone)]
+pub struct BulletBlueprint(pub i8, pub Trajectory, pub f32, pub bool, pub i64);
 
-/// Gun Blueprint for constant storage of certain gun types:  (**shoot_delay**:*f32*, **damage**:*i64*, **max_bullets**:*u8*, **max_ammo**:*usize*, **reload_delay**:*usize*)
-pub struct GunBluePrint(pub f32, pub i64, pub u8, pub u64, pub f32);
+/// Gun Blueprint for constant storage of certain gun types:  (**shoot_delay**:*f32*, **damage**:*i64*, **max_bullets**:*u8*, **max_ammo**:*usize*, **reload_delay**:*usize*, **aim**:*Aim*)
+pub struct GunBluePrint(pub f32, pub i64, pub u8, pub u64, pub f32, pub Aim);
 
 #[derive(Component)]
 /// 
@@ -26,7 +29,8 @@ pub struct Gun {
     ammo: u64,
     max_ammo: u64,
     pub reload_stopwatch: Timer,
-    reload_delay: f32
+    reload_delay: f32,
+    aim: Aim
 }
 
 impl Gun {
@@ -40,7 +44,8 @@ impl Gun {
             ammo: ammo,
             max_ammo: ammo,
             reload_stopwatch: Timer::new(Duration::from_secs_f32(reload_delay), TimerMode::Once),
-            reload_delay: reload_delay
+            reload_delay: reload_delay,
+            aim: Aim::Fixed
         }
     }
 
@@ -54,7 +59,8 @@ impl Gun {
             ammo: gun_blueprint.3,
             max_ammo: gun_blueprint.3,
             reload_stopwatch: Timer::new(Duration::from_secs_f32(gun_blueprint.4), TimerMode::Once),
-            reload_delay: gun_blueprint.4
+            reload_delay: gun_blueprint.4,
+            aim: gun_blueprint.5
         }
     }
 
@@ -66,6 +72,8 @@ impl Gun {
 
     pub fn get_bullets(&self) -> &Vec<BulletBlueprint> { &self.bullet_blueprints }
 
+    pub fn get_aim(&self) -> Aim { self.aim }
+
     pub fn set_bullet_delay(&mut self, new_delay: f32) {
 
         self.shoot_delay = if  new_delay >= 0.03 {new_delay} else {0.03};
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bevy::{app::AppExit, input::InputPlugin, log::LogPlugin, prelude::*, time::TimeUpdateStrategy, utils::Instant};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{bullet::{Bullet, Plasma}, bullet_pool::BulletPool, cli::Args, enemy_defs, game::{self, GameTimer, RunSeed, ScoreBoard}, replay::{self, ReplayVerdict}, trajectory::{Expr, Trajectory}, playfield::Playfield, GameState};
+
+/// Drives the simulation without a window: one fixed tick per app update, stops after a tick limit or when the player dies
+pub struct Headless {
+    pub frames: Option<u64>,
+    /// Extra bullets to keep alive for benchmarking collisions
+    pub stress: Option<usize>,
+}
+
+#[derive(Resource)]
+struct TickLimit(Option<u64>);
+
+#[derive(Resource, Default)]
+pub struct SimulatedTicks(pub u64);
+
+impl Plugin for Headless {
+    fn build(&self, app: &mut App){
+        // Step virtual time by exactly one fixed timestep per update so FixedUpdate runs once per frame no matter how fast we go
+        let timestep = Time::<Fixed>::default().timestep();
+
+        app
+            .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+            .insert_resource(TickLimit(self.frames))
+            .init_resource::<SimulatedTicks>()
+            .add_systems(FixedPostUpdate, count_ticks.run_if(game::run_active))
+            .add_systems(OnEnter(GameState::Menu), finish_run)
+            .add_systems(OnEnter(GameState::GameOver), finish_run);
+
+        if let Some(bullets) = self.stress {
+            app
+                .insert_resource(Stress { bullets, rng: StdRng::seed_from_u64(0), started: None, total: Duration::ZERO, worst: Duration::ZERO, ticks: 0 })
+                .add_systems(FixedPreUpdate, top_up_bullets.run_if(game::run_active))
+                .add_systems(First, start_tick_timer)
+                .add_systems(Last, stop_tick_timer);
+        }
+    }
+}
+
+/// Bullets kept alive for `--stress` and how long each update took with them around
+#[derive(Resource)]
+struct Stress { bullets: usize, rng: StdRng, started: Option<Instant>, total: Duration, worst: Duration, ticks: u32 }
+
+/// Build and run the headless app
+pub fn run(args: &Args) {
+    App::new()
+        .add_plugins((MinimalPlugins, LogPlugin::default(), InputPlugin, AssetPlugin::default()))
+        .init_state::<GameState>()
+        .add_plugins(enemy_defs::EnemyDefsPlugin { next_state: GameState::Game })
+        .add_plugins(game::BulletHellElite { seed: args.seed, mode: args.mode() })
+        .add_plugins(replay::ReplayPlugin { replay: args.replay.clone(), record: args.record.clone() })
+        .add_plugins(Headless { frames: args.frames, stress: args.stress })
+        .run();
+}
+
+fn count_ticks(
+    mut ticks: ResMut<SimulatedTicks>,
+    limit: Res<TickLimit>,
+    game_time: Res<GameTimer>,
+    mut score_board: ResMut<ScoreBoard>,
+    mut game_state: ResMut<NextState<GameState>>
+){
+    ticks.0 += 1;
+
+    if limit.0.is_some_and(|l| ticks.0 >= l) {
+        score_board.set_game_time(game_time.0.elapsed_secs());
+        game_state.set(GameState::Menu); // ends the run the same way the player dying does
+    }
+}
+
+/// Report how the run went and quit
+fn finish_run(
+    ticks: Res<SimulatedTicks>,
+    score_board: Res<ScoreBoard>,
+    run_seed: Res<RunSeed>,
+    verdict: Res<ReplayVerdict>,
+    stress: Option<Res<Stress>>,
+    mut exit: EventWriter<AppExit>
+){
+    info!(
+        "run finished after {} ticks: seed {} score {} waves {} grazes {} time {:.2}s",
+        ticks.0, run_seed.seed, score_board.get_score(), score_board.get_waves(), score_board.get_grazes(), score_board.get_game_time()
+    );
+
+    if let Some(stress) = stress {
+        let average = stress.total.as_secs_f64() * 1000. / stress.ticks.max(1) as f64;
+        info!("stress: {}+ bullets, average tick {:.3}ms, worst tick {:.3}ms", stress.bullets, average, stress.worst.as_secs_f64() * 1000.);
+    }
+
+    if *verdict == ReplayVerdict::Diverged {
+        std::process::exit(1); // let CI know the replay no longer reproduces
+    }
+    exit.send(AppExit);
+}
+
+/// Spawn harmless drifting bullets (half the player's, half the enemies') until there are enough alive
+fn top_up_bullets(mut commands: Commands, mut pool: ResMut<BulletPool>, mut stress: ResMut<Stress>, bullets: Query<&Bullet>, playfield: Res<Playfield>){
+    let alive = bullets.iter().filter(|b| b.is_active()).count();
+
+    for i in alive..stress.bullets {
+        let ply = i % 2 == 0;
+        let x = stress.rng.gen_range(playfield.left..playfield.right);
+        let y = stress.rng.gen_range(playfield.bottom..playfield.top);
+        let path = Trajectory::new(Expr::constant(stress.rng.gen_range(-2. ..2.)), Expr::constant(stress.rng.gen_range(-2. ..2.)));
+        let tick = stress.rng.gen_range(0. ..5.); // spread out when they time out
+
+        let dir = if ply { 1 } else { -1 };
+        let plasma = if ply { Plasma::Blue } else { Plasma::Red };
+        pool.spawn(&mut commands, x, y, Bullet::new(dir, path, tick, ply, 0), plasma);
+    }
+}
+
+fn start_tick_timer(mut stress: ResMut<Stress>){
+    stress.started = Some(Instant::now());
+}
+
+fn stop_tick_timer(mut stress: ResMut<Stress>){
+    if let Some(started) = stress.started.take() {
+        let took = started.elapsed();
+        stress.total += took;
+        stress.worst = stress.worst.max(took);
+        stress.ticks += 1;
+    }
+}
@@ -87,6 +87,13 @@ impl Health {
  
     pub fn set_health(&mut self, health: i64){ self.health = health; }
 
+    /// Back to full health and shield (keeps upgrades)
+    pub fn restore(&mut self){
+        self.health = self.max_health;
+        self.shield = self.max_shield;
+        self.is_alive = true;
+    }
+
   
 }
 
@@ -0,0 +1,159 @@
+use bevy::{math::bounding::{Aabb2d, BoundingCircle, BoundingVolume, IntersectsVolume}, prelude::*};
+
+use crate::spatial::Layer;
+
+// Hitbox sizes (radius / half size), players get a tiny one as bullet hells do so dodging through gaps works
+const PLAYER_RADIUS: f32 = 4.;
+const BULLET_RADIUS: f32 = 6.;
+const ENEMY_HALF_SIZE: f32 = 16.; // times the enemy's scale
+const POWER_UP_HALF_SIZE: f32 = 24.;
+
+/// Which layers a hitbox runs into
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct LayerMask(u8);
+
+impl LayerMask {
+    pub const NONE: LayerMask = LayerMask(0);
+
+    pub const fn of(layers: &[Layer]) -> LayerMask {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < layers.len() {
+            bits |= 1 << layers[i] as u8;
+            i += 1;
+        }
+        LayerMask(bits)
+    }
+
+    pub fn contains(self, layer: Layer) -> bool {
+        self.0 & (1 << layer as u8) != 0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Shape {
+    /// Radius
+    Circle(f32),
+    /// Half the width and height
+    Aabb(Vec2),
+}
+
+/// The area something can be hit in, which layer it is on and which layers it runs into
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Hitbox {
+    pub shape: Shape,
+    /// From the entity's position, turns and scales with it
+    pub offset: Vec2,
+    pub layer: Layer,
+    pub mask: LayerMask,
+}
+
+impl Hitbox {
+    pub fn new(shape: Shape, layer: Layer, mask: LayerMask) -> Hitbox {
+        Hitbox { shape, offset: Vec2::ZERO, layer, mask }
+    }
+
+    /// Hurt by enemy bullets and enemies running into it, picks up power ups
+    pub fn player() -> Hitbox {
+        Hitbox::new(Shape::Circle(PLAYER_RADIUS), Layer::Player, LayerMask::of(&[Layer::PowerUp]))
+    }
+
+    /// Hurts the player by running into them
+    pub fn enemy() -> Hitbox {
+        Hitbox::new(Shape::Aabb(Vec2::splat(ENEMY_HALF_SIZE)), Layer::Enemy, LayerMask::of(&[Layer::Player]))
+    }
+
+    /// Player bullets hit enemies and cancel out enemy bullets, enemy bullets hit the player
+    pub fn bullet(ply: bool) -> Hitbox {
+        match ply {
+            true => Hitbox::new(Shape::Circle(BULLET_RADIUS), Layer::PlayerBullet, LayerMask::of(&[Layer::Enemy, Layer::EnemyBullet])),
+            false => Hitbox::new(Shape::Circle(BULLET_RADIUS), Layer::EnemyBullet, LayerMask::of(&[Layer::Player])),
+        }
+    }
+
+    pub fn power_up() -> Hitbox {
+        Hitbox::new(Shape::Aabb(Vec2::splat(POWER_UP_HALF_SIZE)), Layer::PowerUp, LayerMask::NONE)
+    }
+
+    /// Where the hitbox is for an entity at `transform`
+    pub fn volume(&self, transform: &Transform) -> Volume {
+        let center = transform.transform_point(self.offset.extend(0.)).truncate();
+        let scale = transform.scale.truncate().abs();
+
+        match self.shape {
+            Shape::Circle(radius) => Volume::Circle(BoundingCircle::new(center, radius * scale.max_element())),
+            Shape::Aabb(half_size) => Volume::Aabb(Aabb2d::new(center, half_size * scale)),
+        }
+    }
+}
+
+/// A hitbox placed in the world
+#[derive(Clone, Copy, Debug)]
+pub enum Volume {
+    Circle(BoundingCircle),
+    Aabb(Aabb2d),
+}
+
+impl Volume {
+    /// Box around it (what the spatial grid files it under)
+    pub fn aabb(&self) -> Aabb2d {
+        match self {
+            Volume::Circle(circle) => circle.aabb_2d(),
+            Volume::Aabb(aabb) => *aabb,
+        }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        match self {
+            Volume::Circle(circle) => circle.center(),
+            Volume::Aabb(aabb) => aabb.center(),
+        }
+    }
+
+    pub fn intersects(&self, other: &Volume) -> bool {
+        match (self, other) {
+            (Volume::Circle(a), Volume::Circle(b)) => a.intersects(b),
+            (Volume::Circle(a), Volume::Aabb(b)) => a.intersects(b),
+            (Volume::Aabb(a), Volume::Circle(b)) => a.intersects(b),
+            (Volume::Aabb(a), Volume::Aabb(b)) => a.intersects(b),
+        }
+    }
+}
+
+impl From<Aabb2d> for Volume {
+    fn from(aabb: Aabb2d) -> Volume { Volume::Aabb(aabb) }
+}
+
+impl From<BoundingCircle> for Volume {
+    fn from(circle: BoundingCircle) -> Volume { Volume::Circle(circle) }
+}
+
+/// Whether hitboxes are drawn over everything, F3 toggles it
+#[derive(Resource, Default)]
+pub struct HitboxDebug(bool);
+
+pub fn toggle_hitbox_debug(keyboard_input: Res<ButtonInput<KeyCode>>, mut debug: ResMut<HitboxDebug>){
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        debug.0 = !debug.0;
+    }
+}
+
+pub fn draw_hitboxes(mut gizmos: Gizmos, debug: Res<HitboxDebug>, hitboxes: Query<(&Transform, &Hitbox, &ViewVisibility)>){
+    if !debug.0 { return; }
+
+    for (transform, hitbox, visibility) in &hitboxes {
+        if !visibility.get() { continue; } // pooled bullets waiting to be fired again
+
+        let color = match hitbox.layer {
+            Layer::Player => Color::YELLOW,
+            Layer::Enemy => Color::RED,
+            Layer::PlayerBullet => Color::CYAN,
+            Layer::EnemyBullet => Color::ORANGE,
+            Layer::PowerUp => Color::GREEN,
+        };
+        match hitbox.volume(transform) {
+            Volume::Circle(circle) => { gizmos.circle_2d(circle.center(), circle.radius(), color); },
+            Volume::Aabb(aabb) => { gizmos.rect_2d(aabb.center(), 0., aabb.half_size() * 2., color); },
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+
+use crate::{
+    bomb::{Bombs, MAX_BOMBS}, game::ScoreBoard, gun::Gun, health::Health, lives::{Lives, MAX_LIVES}, player::PlayerControlled,
+    power_ups::Inventory, shapes::ShapeGun
+};
+
+const BAR_WIDTH: f32 = 220.;
+const BAR_HEIGHT: f32 = 12.;
+const ICON_SIZE: f32 = 26.;
+/// Multiplier the bar fills up at (player bullet damage stops growing there)
+const MUL_CAP: u64 = 13;
+
+const BAR_BACKGROUND: Color = Color::rgba(0.2, 0.2, 0.2, 0.8);
+const RELOADING: Color = Color::ORANGE;
+/// Shield colour while it waits after a hit before recharging
+const SHIELD_WAITING: Color = Color::rgb(0.1, 0.45, 0.55);
+
+/// What a bar shows
+#[derive(Clone, Copy)]
+pub enum Meter {
+    Health,
+    Shield,
+    Ammo,
+    Shapes,
+    Multiplier,
+}
+
+#[derive(Component)]
+pub struct MeterFill(Meter);
+
+#[derive(Component)]
+pub struct MeterLabel(Meter);
+
+/// Row of icons, one per bomb / life left
+#[derive(Component, Clone, Copy)]
+pub enum IconRow {
+    Bombs,
+    Lives,
+}
+
+#[derive(Component)]
+pub struct WaveText;
+
+/// Column listing the power ups picked up this run
+#[derive(Component)]
+pub struct InventoryList;
+
+/// Health / shield bars and lives bottom right, ammo / shape bars and bombs bottom left, wave and multiplier top left, power ups down the right
+pub fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>){
+    let font = asset_server.load("fonts/EvilEmpire.otf");
+
+    let panel = |top: Option<f32>, bottom: Option<f32>, left: Option<f32>, right: Option<f32>| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: top.map_or(Val::Auto, Val::Px),
+            bottom: bottom.map_or(Val::Auto, Val::Px),
+            left: left.map_or(Val::Auto, Val::Px),
+            right: right.map_or(Val::Auto, Val::Px),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            ..default()
+        },
+        ..default()
+    };
+
+    commands.spawn(panel(None, Some(20.), Some(20.), None)).with_children(|parent| {
+        spawn_meter(parent, &font, Meter::Ammo);
+        spawn_meter(parent, &font, Meter::Shapes);
+        spawn_icons(parent, asset_server.load("power_ups/red_base.png"), IconRow::Bombs, MAX_BOMBS);
+    });
+
+    commands.spawn(panel(None, Some(20.), None, Some(20.))).with_children(|parent| {
+        spawn_meter(parent, &font, Meter::Health);
+        spawn_meter(parent, &font, Meter::Shield);
+        spawn_icons(parent, asset_server.load("player.png"), IconRow::Lives, MAX_LIVES);
+    });
+
+    commands.spawn(panel(Some(90.), None, Some(20.), None)).with_children(|parent| {
+        parent.spawn((TextBundle::from_section("", TextStyle { font: font.clone(), font_size: 28., color: Color::GOLD }), WaveText));
+        spawn_meter(parent, &font, Meter::Multiplier);
+    });
+
+    commands.spawn((panel(Some(130.), None, None, Some(20.)), InventoryList));
+}
+
+/// A label with a bar under it
+fn spawn_meter(parent: &mut ChildBuilder, font: &Handle<Font>, meter: Meter){
+    parent.spawn((
+        TextBundle::from_section("", TextStyle { font: font.clone(), font_size: 22., color: Color::rgb(0.9, 0.9, 0.9) }),
+        MeterLabel(meter)
+    ));
+    parent
+        .spawn(NodeBundle {
+            style: Style { width: Val::Px(BAR_WIDTH), height: Val::Px(BAR_HEIGHT), ..default() },
+            background_color: BAR_BACKGROUND.into(),
+            ..default()
+        })
+        .with_children(|bar| {
+            bar.spawn((
+                NodeBundle {
+                    style: Style { width: Val::Percent(100.), height: Val::Percent(100.), ..default() },
+                    ..default()
+                },
+                MeterFill(meter)
+            ));
+        });
+}
+
+/// As many icons as there can ever be, the ones past the count are hidden
+fn spawn_icons(parent: &mut ChildBuilder, image: Handle<Image>, row: IconRow, max: u32){
+    parent
+        .spawn((
+            NodeBundle { style: Style { column_gap: Val::Px(4.), margin: UiRect::top(Val::Px(4.)), ..default() }, ..default() },
+            row
+        ))
+        .with_children(|icons| {
+            for _ in 0..max {
+                icons.spawn(ImageBundle {
+                    style: Style { width: Val::Px(ICON_SIZE), height: Val::Px(ICON_SIZE), ..default() },
+                    image: UiImage::new(image.clone()),
+                    ..default()
+                });
+            }
+        });
+}
+
+/// How full a bar is, its colour and its label
+fn reading(meter: Meter, health: &Health, gun: &Gun, shapes: &ShapeGun, score_board: &ScoreBoard) -> (f32, Color, String) {
+    let fraction = |n: i64, max: i64| n.max(0) as f32 / max.max(1) as f32;
+
+    match meter {
+        Meter::Health => (fraction(health.get_health(), health.get_max_health()), Color::GREEN, format!("HP {}/{}", health.get_health(), health.get_max_health())),
+        Meter::Shield => {
+            let recharging = health.can_shield_recharge() && health.get_shield() < health.get_max_shield();
+            let label = format!("SHIELD {}/{}{}", health.get_shield(), health.get_max_shield(), if recharging { "  RECHARGING" } else { "" });
+            let color = if health.can_shield_recharge() { Color::CYAN } else { SHIELD_WAITING };
+            (fraction(health.get_shield(), health.get_max_shield()), color, label)
+        },
+        Meter::Ammo if gun.get_ammo() == 0 => (gun.reload_stopwatch.fraction(), RELOADING, String::from("AMMO  RELOADING")),
+        Meter::Ammo => (fraction(gun.get_ammo() as i64, gun.get_max_ammo() as i64), Color::WHITE, format!("AMMO {}/{}", gun.get_ammo(), gun.get_max_ammo())),
+        Meter::Shapes if shapes.get_shots() == 0 => (shapes.timer.fraction(), RELOADING, String::from("SHAPES  RELOADING")),
+        Meter::Shapes => (fraction(shapes.get_shots() as i64, shapes.get_max_shots() as i64), Color::LIME_GREEN, format!("SHAPES {}/{}", shapes.get_shots(), shapes.get_max_shots())),
+        Meter::Multiplier => (fraction(score_board.get_mul().min(MUL_CAP) as i64, MUL_CAP as i64), Color::GOLD, format!("MULTIPLIER x{}", score_board.get_mul())),
+    }
+}
+
+pub fn update_meters(
+    player: Query<(&Health, &Gun, &ShapeGun), With<PlayerControlled>>,
+    score_board: Res<ScoreBoard>,
+    mut fills: Query<(&MeterFill, &mut Style, &mut BackgroundColor)>,
+    mut labels: Query<(&MeterLabel, &mut Text)>
+){
+    let Ok((health, gun, shapes)) = player.get_single() else { return; };
+
+    for (fill, mut style, mut color) in &mut fills {
+        let (fraction, fill_color, _) = reading(fill.0, health, gun, shapes, &score_board);
+        style.width = Val::Percent(fraction * 100.);
+        *color = fill_color.into();
+    }
+    for (label, mut text) in &mut labels {
+        let (_, _, value) = reading(label.0, health, gun, shapes, &score_board);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+pub fn update_icons(
+    bombs: Query<&Bombs, With<PlayerControlled>>,
+    lives: Res<Lives>,
+    rows: Query<(&IconRow, &Children)>,
+    mut icons: Query<&mut Visibility>
+){
+    let bombs = bombs.get_single().map_or(0, |bombs| bombs.get());
+
+    for (row, children) in &rows {
+        let count = match row {
+            IconRow::Bombs => bombs,
+            IconRow::Lives => lives.get(),
+        };
+        for (i, child) in children.iter().enumerate() {
+            let Ok(mut visibility) = icons.get_mut(*child) else { continue; };
+            let wanted = if (i as u32) < count { Visibility::Inherited } else { Visibility::Hidden };
+            if *visibility != wanted {
+                *visibility = wanted;
+            }
+        }
+    }
+}
+
+pub fn update_wave(score_board: Res<ScoreBoard>, mut text_query: Query<&mut Text, With<WaveText>>){
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("WAVE {}", score_board.get_waves());
+    }
+}
+
+/// Rebuild the power up list when something new is picked up
+pub fn update_inventory(
+    mut commands: Commands,
+    inventory: Res<Inventory>,
+    asset_server: Res<AssetServer>,
+    list: Query<Entity, With<InventoryList>>
+){
+    if !inventory.is_changed() { return; }
+    let font = asset_server.load("fonts/EvilEmpire.otf");
+
+    for entity in &list {
+        commands.entity(entity).despawn_descendants().with_children(|parent| {
+            for (power_up, count) in inventory.iter() {
+                parent
+                    .spawn(NodeBundle { style: Style { align_items: AlignItems::Center, column_gap: Val::Px(6.), ..default() }, ..default() })
+                    .with_children(|row| {
+                        row.spawn(ImageBundle {
+                            style: Style { width: Val::Px(ICON_SIZE), height: Val::Px(ICON_SIZE), ..default() },
+                            image: UiImage::new(asset_server.load(power_up.value())),
+                            ..default()
+                        });
+                        row.spawn(TextBundle::from_section(format!("x{}", count), TextStyle { font: font.clone(), font_size: 24., color: Color::WHITE }));
+                    });
+            }
+        });
+    }
+}
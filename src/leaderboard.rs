@@ -0,0 +1,403 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{prelude::*, window::ReceivedCharacter};
+use serde::{Deserialize, Serialize};
+
+use crate::{game::{GameMode, RunSeed, ScoreBoard}, menu::{MenuState, MenuView}, replay::InputSource, storage, GameState};
+
+const LEADERBOARD_FILE: &str = "leaderboard.ron";
+const MAX_ENTRIES: usize = 10;
+const MAX_NAME_LEN: usize = 12;
+const DEFAULT_NAME: &str = "Pilot";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: u64,
+    pub waves: u64,
+    /// Seconds survived
+    pub time: f32,
+    /// Unix timestamp of when the run ended
+    pub date: u64,
+    pub seed: u64,
+    /// Endless and campaign runs score differently, each has its own table (entries from before there was a campaign are endless)
+    #[serde(default)]
+    pub mode: GameMode,
+}
+
+/// Best runs on this machine for each mode, highest score first, saved in the data dir
+#[derive(Resource, Serialize, Deserialize, Default)]
+pub struct Leaderboard { entries: Vec<LeaderboardEntry> }
+
+impl Leaderboard {
+    /// Load the saved table, an empty one if there isn't one (or it can't be read)
+    pub fn load() -> Leaderboard {
+        let mut leaderboard: Leaderboard = storage::load_ron(LEADERBOARD_FILE).unwrap_or_default();
+        // the file might have been edited by hand
+        leaderboard.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        leaderboard.trim();
+        leaderboard
+    }
+
+    /// Keep the best `MAX_ENTRIES` of each mode
+    fn trim(&mut self) {
+        let mut kept = [0; 2];
+        self.entries.retain(|entry| {
+            let count = &mut kept[entry.mode as usize];
+            *count += 1;
+            *count <= MAX_ENTRIES
+        });
+    }
+
+    pub fn save(&self) {
+        storage::save_ron(LEADERBOARD_FILE, self);
+    }
+
+    pub fn qualifies(&self, mode: GameMode, score: u64) -> bool {
+        let table = self.entries(mode);
+        score > 0 && (table.len() < MAX_ENTRIES || table.last().is_some_and(|last| score > last.score))
+    }
+
+    /// Add a run to its mode's table, returns its place there (ties go under the older run)
+    pub fn insert(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        let mode = entry.mode;
+        let at = self.entries.iter().position(|e| entry.score > e.score).unwrap_or(self.entries.len());
+        let rank = self.entries[..at].iter().filter(|e| e.mode == mode).count();
+        self.entries.insert(at, entry);
+        self.trim();
+        if rank < MAX_ENTRIES { Some(rank) } else { None }
+    }
+
+    /// One mode's table, best first
+    pub fn entries(&self, mode: GameMode) -> Vec<&LeaderboardEntry> {
+        self.entries.iter().filter(|entry| entry.mode == mode).collect()
+    }
+}
+
+/// The last run if it made the table and still needs a name
+#[derive(Resource, Default)]
+pub struct PendingScore(pub Option<LeaderboardEntry>);
+
+#[derive(Resource, Default)]
+struct NameInput(String);
+
+/// Row to highlight in the leaderboard (the run that was just entered)
+#[derive(Resource, Default)]
+struct NewEntry(Option<(GameMode, usize)>);
+
+/// Which mode's table the leaderboard screen is showing
+#[derive(Resource, Default)]
+struct ShownTable(GameMode);
+
+#[derive(Component)]
+struct NameText;
+
+pub fn leaderboard_plugin(app: &mut App) {
+    app
+        .insert_resource(Leaderboard::load())
+        .init_resource::<PendingScore>()
+        .init_resource::<NameInput>()
+        .init_resource::<NewEntry>()
+        .init_resource::<ShownTable>()
+        .add_systems(OnExit(GameState::Game), record_finished_run)
+        .add_systems(OnEnter(GameState::Game), forget_pending)
+        .add_systems(OnEnter(MenuState::NameEntry), spawn_name_entry)
+        .add_systems(OnEnter(MenuState::Leaderboard), |mode: Res<GameMode>, mut shown: ResMut<ShownTable>| shown.0 = *mode)
+        .add_systems(OnExit(MenuState::Leaderboard), |mut new_entry: ResMut<NewEntry>| new_entry.0 = None)
+        .add_systems(Update, (
+            open_leaderboard.run_if(in_state(MenuState::Main)),
+            (name_entry_input, update_name_text).chain().run_if(in_state(MenuState::NameEntry)),
+            (close_leaderboard, switch_table, spawn_leaderboard.run_if(resource_changed::<ShownTable>)).chain().run_if(in_state(MenuState::Leaderboard))
+        ));
+}
+
+/// Remember a run that made the table so the menu can ask for a name (played back replays don't count)
+fn record_finished_run(
+    score_board: Res<ScoreBoard>,
+    run_seed: Res<RunSeed>,
+    source: Res<InputSource>,
+    mode: Res<GameMode>,
+    leaderboard: Res<Leaderboard>,
+    mut pending: ResMut<PendingScore>
+){
+    if !matches!(*source, InputSource::Keyboard) || !leaderboard.qualifies(*mode, score_board.get_score()) { return; }
+
+    pending.0 = Some(LeaderboardEntry {
+        name: String::new(),
+        score: score_board.get_score(),
+        waves: score_board.get_waves(),
+        time: score_board.get_game_time(),
+        date: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        seed: run_seed.seed,
+        mode: *mode,
+    });
+}
+
+/// A restarted run never went back to the menu, its score is dropped
+fn forget_pending(mut pending: ResMut<PendingScore>){
+    pending.0 = None;
+}
+
+fn name_entry_input(
+    mut characters: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut name: ResMut<NameInput>,
+    mut pending: ResMut<PendingScore>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut new_entry: ResMut<NewEntry>,
+    mut menu_state: ResMut<NextState<MenuState>>
+){
+    for event in characters.read() {
+        for c in event.char.chars() {
+            if (c.is_alphanumeric() || c == ' ' || c == '-' || c == '_') && name.0.chars().count() < MAX_NAME_LEN {
+                name.0.push(c);
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        name.0.pop();
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        if let Some(mut entry) = pending.0.take() {
+            let trimmed = name.0.trim();
+            entry.name = if trimmed.is_empty() { DEFAULT_NAME.to_string() } else { trimmed.to_string() };
+            let mode = entry.mode;
+            new_entry.0 = leaderboard.insert(entry).map(|rank| (mode, rank));
+            leaderboard.save();
+        }
+        menu_state.set(MenuState::Leaderboard);
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        pending.0 = None;
+        menu_state.set(MenuState::Main);
+    }
+}
+
+fn update_name_text(name: Res<NameInput>, mut text_query: Query<&mut Text, With<NameText>>){
+    if name.is_changed() {
+        for mut text in &mut text_query {
+            text.sections[0].value = format!("{}_", name.0);
+        }
+    }
+}
+
+fn open_leaderboard(keyboard_input: Res<ButtonInput<KeyCode>>, mut menu_state: ResMut<NextState<MenuState>>){
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        menu_state.set(MenuState::Leaderboard);
+    }
+}
+
+/// Tab flips between the endless and campaign tables
+fn switch_table(keyboard_input: Res<ButtonInput<KeyCode>>, mut shown: ResMut<ShownTable>){
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        shown.0 = match shown.0 { GameMode::Endless => GameMode::Campaign, GameMode::Campaign => GameMode::Endless };
+    }
+}
+
+fn close_leaderboard(keyboard_input: Res<ButtonInput<KeyCode>>, mut menu_state: ResMut<NextState<MenuState>>){
+    if keyboard_input.any_just_pressed([KeyCode::KeyL, KeyCode::Escape]) {
+        menu_state.set(MenuState::Main);
+    }
+}
+
+fn text_style(asset_server: &AssetServer, size: f32, color: Color) -> TextStyle {
+    TextStyle { font: asset_server.load("fonts/EvilEmpire.otf"), font_size: size, color }
+}
+
+fn view_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(16.0),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn spawn_name_entry(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    pending: Res<PendingScore>,
+    mut name: ResMut<NameInput>,
+    mut characters: ResMut<Events<ReceivedCharacter>>
+){
+    name.0.clear();
+    characters.clear(); // don't type whatever was pressed at the end of the run
+    let score = pending.0.as_ref().map(|entry| entry.score).unwrap_or(0);
+
+    commands.spawn((view_root(), MenuView)).with_children(|parent| {
+        parent.spawn(TextBundle::from_section("New High Score!", text_style(&asset_server, 80., Color::GOLD)));
+        parent.spawn(TextBundle::from_section(format!("{}", score), text_style(&asset_server, 60., Color::GOLD)));
+        parent.spawn(TextBundle::from_section("Enter your name:", text_style(&asset_server, 40., Color::rgb(0.9, 0.9, 0.9))));
+        parent.spawn((TextBundle::from_section("_", text_style(&asset_server, 60., Color::WHITE)), NameText));
+        parent.spawn(TextBundle::from_section("Enter to save, Escape to skip", text_style(&asset_server, 30., Color::GRAY)));
+    });
+}
+
+const COLUMNS: [(&str, f32); 7] = [("#", 50.), ("Name", 220.), ("Score", 180.), ("Waves", 100.), ("Time", 100.), ("Date", 170.), ("Seed", 260.)];
+
+fn spawn_leaderboard(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    leaderboard: Res<Leaderboard>,
+    new_entry: Res<NewEntry>,
+    shown: Res<ShownTable>,
+    views: Query<Entity, With<MenuView>>
+){
+    for view in &views {
+        commands.entity(view).despawn_recursive(); // the other mode's table
+    }
+
+    let table = leaderboard.entries(shown.0);
+    let rows: Vec<(Color, [String; 7])> = table.iter().enumerate().map(|(i, entry)| {
+        let color = if new_entry.0 == Some((shown.0, i)) { Color::GOLD } else { Color::rgb(0.9, 0.9, 0.9) };
+        (color, [
+            format!("{}", i + 1),
+            entry.name.clone(),
+            format!("{}", entry.score),
+            format!("{}", entry.waves),
+            format!("{}:{:02}", entry.time as u64 / 60, entry.time as u64 % 60),
+            format_date(entry.date),
+            format!("{}", entry.seed),
+        ])
+    }).collect();
+
+    commands.spawn((view_root(), MenuView)).with_children(|parent| {
+        let title = match shown.0 { GameMode::Endless => "Leaderboard: Survive", GameMode::Campaign => "Leaderboard: Campaign" };
+        parent.spawn(TextBundle::from_section(title, text_style(&asset_server, 80., Color::GOLD)));
+
+        let header = (Color::GRAY, COLUMNS.map(|(name, _)| name.to_string()));
+        for (color, cells) in std::iter::once(header).chain(rows) {
+            parent.spawn(NodeBundle { style: Style { flex_direction: FlexDirection::Row, ..default() }, ..default() }).with_children(|row| {
+                for (cell, (_, width)) in cells.into_iter().zip(COLUMNS) {
+                    row.spawn(NodeBundle { style: Style { width: Val::Px(width), ..default() }, ..default() }).with_children(|column| {
+                        column.spawn(TextBundle::from_section(cell, text_style(&asset_server, 36., color)));
+                    });
+                }
+            });
+        }
+
+        if table.is_empty() {
+            parent.spawn(TextBundle::from_section("No runs yet, go survive!", text_style(&asset_server, 40., Color::rgb(0.9, 0.9, 0.9))));
+        }
+        parent.spawn(TextBundle::from_section("Tab for the other mode, L or Escape to go back", text_style(&asset_server, 30., Color::GRAY)));
+    });
+}
+
+/// Unix timestamp to YYYY-MM-DD (UTC)
+fn format_date(timestamp: u64) -> String {
+    // days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (timestamp / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u64) -> LeaderboardEntry {
+        LeaderboardEntry { name: score.to_string(), score, waves: 1, time: 1., date: 0, seed: 0, mode: GameMode::Endless }
+    }
+
+    fn full() -> Leaderboard {
+        let mut leaderboard = Leaderboard::default();
+        for score in (1..=MAX_ENTRIES as u64).map(|n| n * 100) {
+            leaderboard.insert(entry(score));
+        }
+        leaderboard
+    }
+
+    #[test]
+    fn any_score_qualifies_until_full() {
+        let mut leaderboard = Leaderboard::default();
+        assert!(!leaderboard.qualifies(GameMode::Endless, 0));
+        assert!(leaderboard.qualifies(GameMode::Endless, 1));
+
+        leaderboard.insert(entry(500));
+        assert!(leaderboard.qualifies(GameMode::Endless, 1));
+    }
+
+    #[test]
+    fn full_table_boundary() {
+        let mut leaderboard = full();
+        assert_eq!(leaderboard.entries(GameMode::Endless).len(), MAX_ENTRIES);
+        assert_eq!(leaderboard.entries(GameMode::Endless).last().unwrap().score, 100);
+
+        // a tie with the last place goes under it, off the table
+        assert!(!leaderboard.qualifies(GameMode::Endless, 100));
+        assert_eq!(leaderboard.insert(entry(100)), None);
+        assert_eq!(leaderboard.entries(GameMode::Endless).last().unwrap().name, "100");
+
+        assert!(leaderboard.qualifies(GameMode::Endless, 101));
+        assert_eq!(leaderboard.insert(entry(101)), Some(MAX_ENTRIES - 1));
+        assert_eq!(leaderboard.entries(GameMode::Endless).len(), MAX_ENTRIES);
+        assert_eq!(leaderboard.entries(GameMode::Endless).last().unwrap().score, 101);
+    }
+
+    #[test]
+    fn insert_keeps_highest_first_and_ties_under_older_runs() {
+        let mut leaderboard = full();
+        assert_eq!(leaderboard.insert(entry(5000)), Some(0));
+
+        let mut tie = entry(500);
+        tie.name = String::from("newer");
+        assert_eq!(leaderboard.insert(tie), Some(7));
+        assert_eq!(leaderboard.entries(GameMode::Endless)[6].name, "500");
+
+        let scores: Vec<u64> = leaderboard.entries(GameMode::Endless).iter().map(|e| e.score).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]), "{:?}", scores);
+    }
+
+    #[test]
+    fn modes_have_their_own_tables() {
+        let mut leaderboard = full();
+        let campaign = |score| LeaderboardEntry { mode: GameMode::Campaign, ..entry(score) };
+
+        // the endless table being full doesn't keep a low campaign score out
+        assert!(leaderboard.qualifies(GameMode::Campaign, 1));
+        assert_eq!(leaderboard.insert(campaign(1)), Some(0));
+        assert_eq!(leaderboard.insert(campaign(50000)), Some(0));
+        assert_eq!(leaderboard.entries(GameMode::Endless).len(), MAX_ENTRIES);
+        assert_eq!(leaderboard.entries(GameMode::Endless)[0].score, 1000);
+        assert_eq!(leaderboard.entries(GameMode::Campaign).iter().map(|e| e.score).collect::<Vec<_>>(), vec![50000, 1]);
+
+        // filling the campaign table pushes out its own lowest, not endless runs
+        for score in 2..=MAX_ENTRIES as u64 {
+            leaderboard.insert(campaign(score));
+        }
+        assert_eq!(leaderboard.entries(GameMode::Campaign).last().unwrap().score, 2);
+        assert_eq!(leaderboard.entries(GameMode::Endless).len(), MAX_ENTRIES);
+    }
+
+    #[test]
+    fn old_entries_are_endless() {
+        let old: LeaderboardEntry = ron::from_str(r#"(name: "Pilot", score: 10, waves: 1, time: 5.0, date: 0, seed: 3)"#).unwrap();
+        assert_eq!(old.mode, GameMode::Endless);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(86399), "1970-01-01");
+        assert_eq!(format_date(951782400), "2000-02-29");
+        assert_eq!(format_date(951868800), "2000-03-01");
+        assert_eq!(format_date(1703980800), "2023-12-31");
+        assert_eq!(format_date(1709164800 + 86399), "2024-02-29");
+        assert_eq!(format_date(4107542400), "2100-03-01");
+    }
+}
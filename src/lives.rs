@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::{
+    bomb::Invulnerable, bullet::Bullet, bullet_pool::BulletPool, explosion::Explode, game::{Difficulty, GameTimer, ScoreBoard}, health::Health,
+    player::PlayerControlled, playfield::Playfield, sfx::{PlaySfx, Sfx}, GameState
+};
+
+pub const MAX_LIVES: u32 = 9;
+/// Scores that earn an extra life (each only once per run)
+const EXTRA_LIFE_SCORES: [u64; 4] = [50_000, 200_000, 500_000, 1_000_000];
+/// Seconds nothing can hurt the player after coming back
+const RESPAWN_INVULNERABLE_TIME: f32 = 3.;
+/// Enemy bullets this close to the spawn point are cleared when the player comes back
+const SPAWN_CLEAR_RADIUS: f32 = 250.;
+
+/// Ships the player has left (counting the one flying) and how many extra lives they have earned
+#[derive(Resource)]
+pub struct Lives { left: u32, earned: usize }
+
+impl Default for Lives {
+    fn default() -> Self {
+        Lives { left: Difficulty::default().starting_lives(), earned: 0 }
+    }
+}
+
+impl Lives {
+    pub fn get(&self) -> u32 { self.left }
+}
+
+/// The player's health ran out
+#[derive(Event)]
+pub struct PlayerDown;
+
+/// The player came back at a position
+#[derive(Event)]
+pub struct Respawn(pub Vec2);
+
+pub fn reset_lives(mut lives: ResMut<Lives>, difficulty: Res<Difficulty>){
+    *lives = Lives { left: difficulty.starting_lives(), earned: 0 };
+}
+
+/// Take a life and bring the player back at the spawn point, blinking and untouchable for a bit (unless that was the last one)
+#[allow(clippy::too_many_arguments)]
+pub fn lose_life(
+    mut downs: EventReader<PlayerDown>,
+    mut lives: ResMut<Lives>,
+    mut player: Query<(Entity, &mut Transform, &mut Health), With<PlayerControlled>>,
+    mut commands: Commands,
+    mut respawns: EventWriter<Respawn>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut explosions: EventWriter<Explode>,
+    playfield: Res<Playfield>
+){
+    // every hit after the killing one in the same tick reports it again
+    if downs.read().count() == 0 { return; }
+    let Ok((entity, mut transform, mut health)) = player.get_single_mut() else { return; };
+
+    lives.left = lives.left.saturating_sub(1);
+    explosions.send(Explode(transform.translation, Color::RED));
+    sfx.send(PlaySfx(Sfx::Hit));
+    if lives.left == 0 { return; }
+
+    let spawn = playfield.player_spawn();
+    transform.translation.x = spawn.x;
+    transform.translation.y = spawn.y + 1.;
+    health.restore();
+    commands.entity(entity).insert(Invulnerable::new(RESPAWN_INVULNERABLE_TIME));
+    respawns.send(Respawn(transform.translation.truncate()));
+    sfx.send(PlaySfx(Sfx::Warp));
+}
+
+/// Give the player room to breathe where they come back
+pub fn clear_spawn_area(
+    mut respawns: EventReader<Respawn>,
+    mut bullets: Query<(Entity, &mut Bullet, &mut Visibility, &Transform), Without<PlayerControlled>>,
+    mut pool: ResMut<BulletPool>
+){
+    for respawn in respawns.read() {
+        for (entity, mut bullet, mut visibility, transform) in &mut bullets {
+            if bullet.is_player() || !bullet.is_active() { continue; }
+            if transform.translation.truncate().distance(respawn.0) < SPAWN_CLEAR_RADIUS {
+                pool.release(entity, &mut bullet, &mut visibility);
+            }
+        }
+    }
+}
+
+/// End the run once the last life is gone
+pub fn game_over(
+    lives: Res<Lives>,
+    game_time: Res<GameTimer>,
+    mut score_board: ResMut<ScoreBoard>,
+    mut game_state: ResMut<NextState<GameState>>
+){
+    if lives.left == 0 {
+        score_board.set_game_time(game_time.0.elapsed_secs());
+        game_state.set(GameState::GameOver);
+    }
+}
+
+pub fn award_extra_lives(score_board: Res<ScoreBoard>, mut lives: ResMut<Lives>, mut sfx: EventWriter<PlaySfx>){
+    while lives.earned < EXTRA_LIFE_SCORES.len() && score_board.get_score() >= EXTRA_LIFE_SCORES[lives.earned] {
+        lives.earned += 1;
+        lives.left = (lives.left + 1).min(MAX_LIVES);
+        sfx.send(PlaySfx(Sfx::Warp));
+    }
+}
@@ -1,53 +1,78 @@
 #![windows_subsystem = "windows"]
 use bevy::{diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin}, prelude::*, window::{PresentMode, WindowTheme}};
-use bevy_hanabi::prelude::*;
 
-pub const T_BOUND: u16 = 400;
-pub const B_BOUND: f32 = -500.;
-const L_BOUND: u16 = 500;
-const R_BOUND: u16 = 500;
 pub const PLAYBACK_SPEED: f32 = 2.0;
 pub const PLAYBACK_VOL: f32 = 0.15;
 
-const RES_X: f32 = 1040.;
-const RES_Y: f32 = 960.;
-
+mod aim;
 mod bullet;
 mod enemy;
+mod bomb;
+mod boss;
+mod campaign;
+mod formation;
 mod player;
+mod playfield;
 mod game;
 mod menu;
 mod music;
 mod health;
+mod hitbox;
+mod hud;
 mod gun;
+mod graze;
 mod explosion;
 mod shapes;
 mod power_ups;
+mod render;
+mod sfx;
+mod cli;
+mod headless;
+mod replay;
+mod enemy_defs;
+mod trajectory;
+mod spatial;
+mod spawner;
+mod bullet_pool;
+mod pause;
+mod storage;
+mod leaderboard;
+mod lives;
+mod game_over;
+mod controls;
+mod settings;
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
 enum GameState {
     #[default]
+    Loading,
     Menu,
     Game,
+    /// The run just ended with the last life, showing how it went before going back to the menu
+    GameOver,
+    /// Passed through for a frame to restart a run (entering the state you're already in does nothing)
+    Restarting,
 }
 
 fn main() {
+    let args = cli::Args::parse();
+
+    if args.headless {
+        headless::run(&args);
+        return;
+    }
+
     App::new()
         .add_plugins((
             DefaultPlugins.set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "BH: Elite".into(),
                     name: Some("BulletHellElite".into()),
-                    resolution: (RES_X, RES_Y).into(),
+                    resolution: (playfield::VIEW_WIDTH, playfield::VIEW_HEIGHT).into(),
                     present_mode: PresentMode::AutoVsync,
                     // Tells wasm not to override default event handling, like F5, Ctrl+R etc.
                     prevent_default_event_handling: false,
                     window_theme: Some(WindowTheme::Dark),
-                    enabled_buttons: bevy::window::EnabledButtons {
-                        maximize: false,
-                        ..Default::default()
-                    },
-                    resize_constraints: WindowResizeConstraints { min_width: RES_X, min_height: RES_Y, max_width: RES_X, max_height: RES_Y },
                     
                     // This will spawn an invisible window
                     // The window will be made visible in the make_visible() system after 3 frames.
@@ -60,14 +85,22 @@ fn main() {
             /* LogDiagnosticsPlugin::default(),
             FrameTimeDiagnosticsPlugin, */
         ))
-        .add_plugins(HanabiPlugin)
         .init_state::<GameState>()
+        .add_plugins(enemy_defs::EnemyDefsPlugin { next_state: GameState::Menu })
         .add_plugins(menu::menu_plugin)
-        .add_plugins(game::BulletHellElite)
+        .add_plugins(pause::pause_plugin)
+        .add_plugins(game_over::game_over_plugin)
+        .add_plugins(leaderboard::leaderboard_plugin)
+        .add_plugins(controls::controls_plugin)
+        .add_plugins(settings::settings_plugin)
+        .add_plugins(game::BulletHellElite { seed: args.seed, mode: args.mode() })
+        .add_plugins(replay::ReplayPlugin { replay: args.replay, record: args.record })
+        .add_plugins(render::BulletHellRender)
         .add_plugins(music::make_plugin)
         .run();
 }
 
+/// HUD text placed from the top right of the view (the UI scales with the letterboxed view so it stays put at any window size)
 #[derive(Bundle)]
 pub struct EzTextBundle<T: Component> {
     text: TextBundle,
@@ -1,40 +1,131 @@
 use bevy::prelude::*;
-use crate::game::ScoreBoard;
+use crate::{campaign::CampaignRun, controls::{Action, Bindings}, game::{GameMode, RunSeed, ScoreBoard}, leaderboard::PendingScore};
 
 use super::GameState;
 
+/// Which screen of the menu is up, `Closed` whenever we aren't in `GameState::Menu`
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
+pub enum MenuState {
+    #[default]
+    Closed,
+    Main,
+    NameEntry,
+    Leaderboard,
+    Controls,
+    Settings,
+}
+
+/// Root of the ui for the current menu screen
+#[derive(Component)]
+pub struct MenuView;
 
 pub fn menu_plugin(app: &mut App) {
     app
-        .add_systems(OnEnter(GameState::Menu), setup)
-        .add_systems(Update, (button_system).run_if(in_state(GameState::Menu)))
+        .init_state::<MenuState>()
+        .add_systems(OnEnter(GameState::Menu), open_menu)
+        .add_systems(OnEnter(MenuState::Main), setup)
+        .add_systems(Update, (button_system, seed_input).run_if(in_state(MenuState::Main)))
+        .add_systems(OnExit(MenuState::Main), despawn_view)
+        .add_systems(OnExit(MenuState::NameEntry), despawn_view)
+        .add_systems(OnExit(MenuState::Leaderboard), despawn_view)
+        .add_systems(OnExit(MenuState::Controls), despawn_view)
+        .add_systems(OnExit(MenuState::Settings), despawn_view)
         .add_systems(OnExit(GameState::Menu), cleanup);
 }
 
+/// Ask for a name first if the last run made the leaderboard
+fn open_menu(mut commands: Commands, pending: Res<PendingScore>, mut menu_state: ResMut<NextState<MenuState>>){
+    // ui camera
+    commands.spawn(Camera2dBundle::default());
+    menu_state.set(if pending.0.is_some() { MenuState::NameEntry } else { MenuState::Main });
+}
+
+fn despawn_view(mut commands: Commands, query: Query<Entity, With<MenuView>>){
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+];
+
+#[derive(Component)]
+struct SeedText;
+
+fn seed_text(run_seed: &RunSeed) -> String {
+    if run_seed.pinned { format!("Seed: {}", run_seed.seed) } else { String::from("Seed: random") }
+}
+
+/// Type digits to pin the seed for the next run, backspace to go back to a random one
+fn seed_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut run_seed: ResMut<RunSeed>,
+    mut text_query: Query<&mut Text, With<SeedText>>
+){
+    for (digit, key) in DIGIT_KEYS.iter().enumerate() {
+        if keyboard_input.just_pressed(*key) {
+            let current = if run_seed.pinned { run_seed.seed } else { 0 };
+            if let Some(seed) = current.checked_mul(10).and_then(|s| s.checked_add(digit as u64)) {
+                run_seed.seed = seed;
+                run_seed.pinned = true;
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Backspace) && run_seed.pinned {
+        run_seed.seed /= 10;
+        run_seed.pinned = run_seed.seed != 0;
+    }
+
+    if run_seed.is_changed() {
+        for mut text in &mut text_query {
+            text.sections[0].value = seed_text(&run_seed);
+        }
+    }
+}
+
+/// Which mode a play button starts
+#[derive(Component, Clone, Copy)]
+struct PlayButton(GameMode);
+
+impl PlayButton {
+    fn label(&self) -> &'static str {
+        match self.0 {
+            GameMode::Endless => "Survive",
+            GameMode::Campaign => "Campaign",
+        }
+    }
+}
+
 fn button_system(
     mut interaction_query: Query<
         (
             &Interaction,
+            &PlayButton,
             &mut BackgroundColor,
             &mut BorderColor,
             &Children,
         ),
-        (Changed<Interaction>, With<Button>),
+        Changed<Interaction>,
     >,
     mut text_query: Query<&mut Text>,
+    mut mode: ResMut<GameMode>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
-    for (interaction, mut color, mut border_color, children) in &mut interaction_query {
+    for (interaction, button, mut color, mut border_color, children) in &mut interaction_query {
         let mut text = text_query.get_mut(children[0]).unwrap();
         match *interaction {
             Interaction::Pressed => {
                 text.sections[0].value = "Sorry!".to_string();
                 *color = PRESSED_BUTTON.into();
                 border_color.0 = Color::RED;
+                *mode = button.0;
                 game_state.set(GameState::Game)
             }
             Interaction::Hovered => {
@@ -43,7 +134,7 @@ fn button_system(
                 border_color.0 = Color::WHITE;
             }
             Interaction::None => {
-                text.sections[0].value = "Survive".to_string();
+                text.sections[0].value = button.label().to_string();
                 *color = Color::SEA_GREEN.into();
                 border_color.0 = Color::GOLD;
             }
@@ -51,16 +142,36 @@ fn button_system(
     }
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<ScoreBoard>) {
-
+/// How the last run went, campaign runs say how far they got
+pub fn last_run_text(score: &ScoreBoard, run_seed: &RunSeed, mode: GameMode, campaign: &CampaignRun) -> String {
     let elapsed_secs = score.get_game_time();
     let minutes = elapsed_secs as u64 / 60;
     let seconds = elapsed_secs as u64 % 60;
-    
-    // ui camera
-    commands.spawn(Camera2dBundle::default());
+
+    let progress = match mode {
+        GameMode::Endless => format!("Waves: {}", score.get_waves()),
+        GameMode::Campaign if campaign.cleared() => String::from("Campaign cleared!"),
+        GameMode::Campaign => format!("Levels: {}/{}", campaign.progress().0, campaign.progress().1),
+    };
+    format!("Last Score: {}\nTime: {}:{}\n{}\nGrazes: {}\nLast Seed: {}", score.get_score(), minutes, seconds, progress, score.get_grazes(), run_seed.seed)
+}
+
+/// Controls (with whatever keys are bound now) and how to play
+fn help_text(bindings: &Bindings) -> String {
+    let keys = |action| bindings.keys(action);
+    format!(
+        "{}/{}/{}/{} to Move\n{} to shoot\n{} for Special\n{} for a Bomb\n{} to Reload\nHold {} to boost\n{} to Pause\n{} and {} for Music Volume\n\
+        L for the Leaderboard\nC to change Controls\nO for Settings\nSurvive: No Objectives just Survive and Score!\nCampaign: Clear the Levels\n\n\
+        Powerups Spawn when a wave is cleared\nBlue = Bullets,\nGreen = Shapes,\nRed = Health",
+        keys(Action::Up), keys(Action::Left), keys(Action::Down), keys(Action::Right), keys(Action::Fire), keys(Action::Special), keys(Action::Bomb),
+        keys(Action::Reload), keys(Action::Boost), keys(Action::Pause), keys(Action::VolumeUp), keys(Action::VolumeDown)
+    )
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<ScoreBoard>, run_seed: Res<RunSeed>, mode: Res<GameMode>, campaign: Res<CampaignRun>, bindings: Res<Bindings>) {
+
     commands
-    .spawn(NodeBundle {
+    .spawn((NodeBundle {
         style: Style {
             width: Val::Percent(100.0),
             height: Val::Percent(100.0),
@@ -69,7 +180,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Scor
             ..Default::default()
         },
         ..Default::default()
-    })
+    }, MenuView))
     .with_children(|parent| {
         parent
             .spawn(NodeBundle {
@@ -83,32 +194,34 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Scor
                 ..Default::default()
             })
             .with_children(|button_parent| {
-                button_parent
-                    .spawn(ButtonBundle {
-                        style: Style {
-                            width: Val::Percent(33.3),
-                            height: Val::Px(165.0),
-                            position_type: PositionType::Absolute,
-                            top: Val::Percent(70.),
-                            left: Val::Percent(33.3),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..Default::default()
-                        },
-                        border_color: BorderColor(Color::BLACK),
-                        background_color: NORMAL_BUTTON.into(),
-                        ..Default::default()
-                    })
-                    .with_children(|button_text_parent| {
-                        button_text_parent.spawn(TextBundle::from_section(
-                            "Button",
-                            TextStyle {
-                                font: asset_server.load("fonts/EvilEmpire.otf"),
-                                font_size: 60.0,
-                                color: Color::rgb(0.9, 0.9, 0.9),
+                for (left, button) in [(18., PlayButton(GameMode::Endless)), (52., PlayButton(GameMode::Campaign))] {
+                    button_parent
+                        .spawn((ButtonBundle {
+                            style: Style {
+                                width: Val::Percent(30.),
+                                height: Val::Px(165.0),
+                                position_type: PositionType::Absolute,
+                                top: Val::Percent(70.),
+                                left: Val::Percent(left),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
                             },
-                        ));
-                    });
+                            border_color: BorderColor(Color::BLACK),
+                            background_color: NORMAL_BUTTON.into(),
+                            ..Default::default()
+                        }, button))
+                        .with_children(|button_text_parent| {
+                            button_text_parent.spawn(TextBundle::from_section(
+                                button.label(),
+                                TextStyle {
+                                    font: asset_server.load("fonts/EvilEmpire.otf"),
+                                    font_size: 60.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            ));
+                        });
+                }
             });
 
         parent.spawn(NodeBundle {
@@ -126,7 +239,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Scor
         })
         .with_children(|text_parent| {
             text_parent.spawn(TextBundle::from_section(
-                "WASD to Move\nSpace to shoot\nE for Special\nHold Shift to boost\nUp and Down Arrow for Music Volume\nNo Objectives just Survive and Score!\n\nPowerups Spawn when a wave is cleared\nBlue = Bullets,\nGreen = Shapes,\nRed = Health",
+                help_text(&bindings),
                 TextStyle {
                     font: asset_server.load("fonts/EvilEmpire.otf"),
                     font_size: 40.0,
@@ -137,7 +250,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Scor
         })
         .with_children(|text_parent| {
             text_parent.spawn(TextBundle::from_section(
-                format!("Last Score: {}\nTime: {}:{}\nWaves: {}", score.get_score(), minutes, seconds , score.get_waves()),
+                last_run_text(&score, &run_seed, *mode, &campaign),
                 TextStyle {
                     font: asset_server.load("fonts/EvilEmpire.otf"),
                     font_size: 60.0,
@@ -147,6 +260,24 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Scor
                 
             ));
         });
+
+        parent.spawn((
+            TextBundle::from_section(
+                seed_text(&run_seed),
+                TextStyle {
+                    font: asset_server.load("fonts/EvilEmpire.otf"),
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(92.),
+                left: Val::Percent(2.),
+                ..default()
+            }),
+            SeedText
+        ));
     });
 
      /*
@@ -166,10 +297,11 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Scor
       */
 }
 
-fn cleanup(mut commands: Commands, query: Query<Entity, With<Node>>, cams: Query<Entity, With<Camera>>) {
+fn cleanup(mut commands: Commands, query: Query<Entity, With<MenuView>>, cams: Query<Entity, With<Camera>>, mut menu_state: ResMut<NextState<MenuState>>) {
     for entity in query.iter() {
-        commands.entity(entity).despawn();
+        commands.entity(entity).despawn_recursive();
     }
+    menu_state.set(MenuState::Closed);
 
     for ent in &cams {
         commands.entity(ent).despawn();
@@ -1,51 +1,274 @@
+use bevy::{asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext}, audio::{PlaybackMode, Volume}, prelude::*, utils::BoxedFuture};
+use serde::Deserialize;
 
+use crate::{boss::Boss, controls::{Action, ActionState}, enemy::Enemy, game::ScoreBoard, pause::PauseState, settings::Settings, GameState};
 
-use bevy::prelude::*;
+const PLAYLIST_PATH: &str = "music/playlist.music.ron";
+const PAUSED_VOLUME: f32 = 0.3; // fraction of the volume the music plays at while paused
+const VOLUME_STEP: f32 = 0.1;
+/// Seconds between looking at how heated the run is (so the music doesn't flip back and forth)
+const INTENSITY_CHECK: f32 = 8.;
+/// Enemies alive at once for a run to count as fully heated
+const FULL_ENEMIES: f32 = 25.;
+/// Waves in for a run to count as fully heated
+const FULL_WAVES: f32 = 12.;
 
+/// Tracks to play for each part of the game, loaded from `assets/music/playlist.music.ron`
+#[derive(Asset, TypePath, Deserialize)]
+pub struct Playlist {
+    /// Played one after another on the menu and game over screens
+    pub menu: Vec<String>,
+    /// Gameplay tracks from calm to intense, how heated the run is picks which one plays
+    pub game: Vec<String>,
+    /// Played one after another while a boss is up
+    pub boss: Vec<String>,
+    /// Seconds one track takes to fade into the next
+    #[serde(default = "default_crossfade")]
+    pub crossfade: f32,
+}
+
+fn default_crossfade() -> f32 { 2. }
+
+impl Playlist {
+    fn tracks(&self, mood: Mood) -> &[String] {
+        match mood {
+            Mood::Menu => &self.menu,
+            Mood::Game => &self.game,
+            Mood::Boss => &self.boss,
+        }
+    }
+
+    /// Track of the `wanted` list to play: gameplay picks by intensity, the others stay on the track playing until it ends
+    fn pick(&self, wanted: Mood, intensity: f32, playing: Option<(Mood, usize)>, finished: bool) -> Option<usize> {
+        let tracks = self.tracks(wanted);
+        if tracks.is_empty() { return None; }
+
+        Some(match playing {
+            _ if wanted == Mood::Game => ((intensity * tracks.len() as f32) as usize).min(tracks.len() - 1),
+            Some((mood, track)) if mood == wanted && finished => (track + 1) % tracks.len(),
+            Some((mood, track)) if mood == wanted => track,
+            _ => 0,
+        })
+    }
+}
+
+/// How heated a run is from 0 to 1, going by enemies alive or waves in (whichever is further along)
+fn intensity(enemies: usize, waves: u64) -> f32 {
+    let crowd = enemies as f32 / FULL_ENEMIES;
+    let progress = waves as f32 / FULL_WAVES;
+    crowd.max(progress).min(1.)
+}
+
+#[derive(Default)]
+pub struct PlaylistLoader;
+
+impl AssetLoader for PlaylistLoader {
+    type Asset = Playlist;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Playlist, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes::<Playlist>(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["music.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct PlaylistHandle(Handle<Playlist>);
+
+/// Which list the music should be playing from
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+enum Mood {
+    #[default]
+    Menu,
+    Game,
+    Boss,
+}
+
+/// What is playing now and what should be
+#[derive(Resource, Default)]
+struct Jukebox {
+    wanted: Mood,
+    /// How heated the run is, 0 to 1
+    intensity: f32,
+    since_check: f32,
+    playing: Option<(Mood, usize, Entity)>,
+}
 
 #[derive(Component)]
 struct Music;
 
+/// Volume of a track (as a fraction of the music volume) fading towards `target` by `speed` a second, tracks faded out are removed
+#[derive(Component)]
+struct Fade { volume: f32, target: f32, speed: f32 }
+
 pub fn make_plugin(app: &mut App){
     app
-        .add_systems(Startup, setup_music)
-        .add_systems(FixedUpdate, music_controls);
+        .init_asset::<Playlist>()
+        .init_asset_loader::<PlaylistLoader>()
+        .init_resource::<Jukebox>()
+        .add_systems(Startup, load_playlist)
+        .add_systems(OnEnter(GameState::Game), |mut jukebox: ResMut<Jukebox>| jukebox.intensity = 0.)
+        .add_systems(Update, (music_controls, pick_mood, switch_tracks, fade_music).chain());
 }
 
-
-
-fn setup_music(mut commands: Commands, asset_server: Res<AssetServer>){
-    commands.spawn(
-        (
-            AudioBundle {
-                source: asset_server.load("sounds/music/bigmusiclmao.ogg"),
-                settings: PlaybackSettings {
-                    mode: bevy::audio::PlaybackMode::Loop,
-                    ..default()
-                }
-            },
-            Music 
-        )
-    );
+fn load_playlist(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands.insert_resource(PlaylistHandle(asset_server.load(PLAYLIST_PATH)));
 }
 
+/// The volume keys change the music volume setting (and save it)
 fn music_controls(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    music_controller: Query<&AudioSink>
+    actions: Res<ActionState>,
+    mut settings: ResMut<Settings>
 )
 {
+    let step = if actions.just_pressed(Action::VolumeUp) { VOLUME_STEP } else if actions.just_pressed(Action::VolumeDown) { -VOLUME_STEP } else { return; };
+
+    settings.music_volume = (settings.music_volume + step).clamp(0., 1.);
+    settings.save();
+}
 
-    
-    if keyboard_input.pressed(KeyCode::ArrowUp) {
-        for sink in music_controller.iter() {
-            sink.set_volume(sink.volume()  + 0.1);
+/// Menu music outside of runs, boss music while a boss is up, otherwise gameplay music as heated as the run (enemies alive and waves in)
+fn pick_mood(
+    time: Res<Time<Real>>,
+    state: Res<State<GameState>>,
+    bosses: Query<(), With<Boss>>,
+    enemies: Query<(), With<Enemy>>,
+    score_board: Res<ScoreBoard>,
+    mut jukebox: ResMut<Jukebox>
+){
+    if *state.get() != GameState::Game {
+        jukebox.wanted = Mood::Menu;
+        return;
+    }
+
+    jukebox.wanted = if bosses.is_empty() { Mood::Game } else { Mood::Boss };
+
+    jukebox.since_check += time.delta_seconds();
+    if jukebox.since_check >= INTENSITY_CHECK {
+        jukebox.since_check = 0.;
+        jukebox.intensity = intensity(enemies.iter().count(), score_board.get_waves());
+    }
+}
+
+/// Crossfade to another track when the mood or intensity calls for one, or the last one in a playlist ended
+fn switch_tracks(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    playlists: Res<Assets<Playlist>>,
+    handle: Res<PlaylistHandle>,
+    mut jukebox: ResMut<Jukebox>,
+    mut music: Query<(&mut Fade, Option<&AudioSink>), With<Music>>
+){
+    let Some(playlist) = playlists.get(&handle.0) else { return; };
+    let tracks = playlist.tracks(jukebox.wanted);
+
+    let finished = jukebox.playing.and_then(|(_, _, entity)| music.get(entity).ok()).is_some_and(|(_, sink)| sink.is_some_and(|sink| sink.empty()));
+    let Some(track) = playlist.pick(jukebox.wanted, jukebox.intensity, jukebox.playing.map(|(mood, track, _)| (mood, track)), finished) else { return; };
+    if !finished && jukebox.playing.is_some_and(|(mood, playing, _)| mood == jukebox.wanted && playing == track) { return; }
+
+    // the same song in another list (or twice in one) carries on instead of fading into a restarted copy of itself
+    let playing_path = jukebox.playing.and_then(|(mood, playing, _)| playlist.tracks(mood).get(playing));
+    if let (false, Some(path), Some((_, _, entity))) = (finished, playing_path, jukebox.playing) {
+        if *path == tracks[track] {
+            jukebox.playing = Some((jukebox.wanted, track, entity));
+            return;
         }
     }
-    if keyboard_input.pressed(KeyCode::ArrowDown) {
-        for sink in music_controller.iter() {
-            let vol = if sink.volume()  - 0.1 < 0. {0. } else {sink.volume()  - 0.1};
-            sink.set_volume(vol);
+
+    let speed = 1. / playlist.crossfade.max(0.01);
+    if let Some((_, _, entity)) = jukebox.playing {
+        if let Ok((mut fade, _)) = music.get_mut(entity) {
+            fade.target = 0.;
         }
     }
-    
-}
\ No newline at end of file
+
+    // gameplay tracks loop until the intensity changes, the other lists move on to the next track
+    let mode = if jukebox.wanted == Mood::Game || tracks.len() == 1 { PlaybackMode::Loop } else { PlaybackMode::Once };
+    let entity = commands.spawn((
+        AudioBundle {
+            source: asset_server.load(tracks[track].clone()),
+            settings: PlaybackSettings { mode, volume: Volume::new(0.), ..default() }
+        },
+        Music,
+        Fade { volume: 0., target: 1., speed }
+    )).id();
+    jukebox.playing = Some((jukebox.wanted, track, entity));
+}
+
+/// Move every track's volume towards where it is fading to (the music volume setting and pausing apply on top)
+fn fade_music(
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    pause: Res<State<PauseState>>,
+    mut commands: Commands,
+    mut music: Query<(Entity, &mut Fade, Option<&AudioSink>), With<Music>>
+){
+    let paused = if *pause.get() == PauseState::Paused { PAUSED_VOLUME } else { 1. };
+
+    for (entity, mut fade, sink) in &mut music {
+        let step = fade.speed * time.delta_seconds();
+        fade.volume = if fade.volume < fade.target { (fade.volume + step).min(fade.target) } else { (fade.volume - step).max(fade.target) };
+
+        if fade.volume <= 0. && fade.target <= 0. {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some(sink) = sink {
+            sink.set_volume(settings.music() * fade.volume * paused);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist() -> Playlist {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        Playlist { menu: names(&["menu_a", "menu_b"]), game: names(&["calm", "busy", "wild"]), boss: names(&["boss"]), crossfade: 2. }
+    }
+
+    #[test]
+    fn intensity_goes_by_enemies_or_waves() {
+        assert_eq!(intensity(0, 0), 0.);
+        assert_eq!(intensity(FULL_ENEMIES as usize / 5, 0), 0.2);
+        assert_eq!(intensity(0, FULL_WAVES as u64 / 2), 0.5);
+        assert_eq!(intensity(FULL_ENEMIES as usize / 5, FULL_WAVES as u64 / 2), 0.5);
+        assert_eq!(intensity(1000, 1000), 1.);
+    }
+
+    #[test]
+    fn game_tracks_follow_intensity() {
+        let playlist = playlist();
+        assert_eq!(playlist.pick(Mood::Game, 0., None, false), Some(0));
+        assert_eq!(playlist.pick(Mood::Game, 0.5, Some((Mood::Game, 0)), false), Some(1));
+        assert_eq!(playlist.pick(Mood::Game, 0.99, Some((Mood::Game, 1)), false), Some(2));
+        assert_eq!(playlist.pick(Mood::Game, 1., Some((Mood::Boss, 0)), true), Some(2));
+    }
+
+    #[test]
+    fn other_lists_play_through_in_order() {
+        let playlist = playlist();
+        assert_eq!(playlist.pick(Mood::Menu, 1., Some((Mood::Game, 2)), false), Some(0));
+        assert_eq!(playlist.pick(Mood::Menu, 1., Some((Mood::Menu, 0)), false), Some(0));
+        assert_eq!(playlist.pick(Mood::Menu, 1., Some((Mood::Menu, 0)), true), Some(1));
+        assert_eq!(playlist.pick(Mood::Menu, 1., Some((Mood::Menu, 1)), true), Some(0));
+        assert_eq!(playlist.pick(Mood::Boss, 0., Some((Mood::Menu, 1)), false), Some(0));
+
+        let silent = Playlist { boss: Vec::new(), ..playlist };
+        assert_eq!(silent.pick(Mood::Boss, 0., None, false), None);
+    }
+}
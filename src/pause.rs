@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+
+use crate::{controls::{Action, ActionState}, game::{GameTimer, ScoreBoard}};
+
+use super::GameState;
+
+/// Whether a run is paused, only means anything while in `GameState::Game`
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+#[derive(Component, Clone, Copy)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Quit,
+}
+
+#[derive(Component)]
+struct PauseOverlay;
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+pub fn pause_plugin(app: &mut App) {
+    app
+        .init_state::<PauseState>()
+        .add_systems(Update, toggle_pause.run_if(in_state(GameState::Game)))
+        .add_systems(Update, button_system.run_if(in_state(PauseState::Paused)))
+        .add_systems(OnEnter(PauseState::Paused), (freeze_time, spawn_overlay))
+        .add_systems(OnExit(PauseState::Paused), (unfreeze_time, despawn_overlay))
+        .add_systems(OnEnter(GameState::Restarting), restart);
+}
+
+fn toggle_pause(
+    actions: Res<ActionState>,
+    state: Res<State<PauseState>>,
+    mut pause_state: ResMut<NextState<PauseState>>
+){
+    if actions.just_pressed(Action::Pause) {
+        pause_state.set(match state.get() {
+            PauseState::Running => PauseState::Paused,
+            PauseState::Paused => PauseState::Running,
+        });
+    }
+}
+
+/// Stopping virtual time stops the fixed timestep, so nothing in the simulation (or any of its timers) ticks while paused
+fn freeze_time(mut time: ResMut<Time<Virtual>>){
+    time.pause();
+}
+
+fn unfreeze_time(mut time: ResMut<Time<Virtual>>){
+    time.unpause();
+}
+
+fn button_system(
+    mut interaction_query: Query<(&Interaction, &PauseButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut score_board: ResMut<ScoreBoard>,
+    game_time: Res<GameTimer>
+){
+    for (interaction, button, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                pause_state.set(PauseState::Running);
+
+                match button {
+                    PauseButton::Resume => {},
+                    PauseButton::Restart => {
+                        score_board.set_game_time(game_time.0.elapsed_secs());
+                        game_state.set(GameState::Restarting);
+                    },
+                    PauseButton::Quit => {
+                        score_board.set_game_time(game_time.0.elapsed_secs());
+                        game_state.set(GameState::Menu);
+                    },
+                }
+            }
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+}
+
+/// Leaving `Game` for a frame is what tears the old run down, entering it again starts a new one
+fn restart(mut game_state: ResMut<NextState<GameState>>){
+    game_state.set(GameState::Game);
+}
+
+fn spawn_overlay(mut commands: Commands, asset_server: Res<AssetServer>){
+    let font = asset_server.load("fonts/EvilEmpire.otf");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(20.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            PauseOverlay
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle { font: font.clone(), font_size: 80.0, color: Color::GOLD },
+            ));
+
+            for (label, button) in [("Resume", PauseButton::Resume), ("Restart", PauseButton::Restart), ("Quit to Menu", PauseButton::Quit)] {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(320.0),
+                                height: Val::Px(80.0),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        },
+                        button
+                    ))
+                    .with_children(|button_parent| {
+                        button_parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle { font: font.clone(), font_size: 48.0, color: Color::rgb(0.9, 0.9, 0.9) },
+                        ));
+                    });
+            }
+        });
+}
+
+fn despawn_overlay(mut commands: Commands, query: Query<Entity, With<PauseOverlay>>){
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
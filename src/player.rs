@@ -4,17 +4,13 @@ use bevy::prelude::*;
 
 
 
-use crate::{bullet, game::{self, GameTimer, ScoreBoard}, gun, health, shapes::{self, ShapeBloop}, GameState};
+use crate::{bomb::Bombs, bullet::{self, Plasma}, bullet_pool::BulletPool, game, gun, health, hitbox::Hitbox, playfield::Playfield, render::SpriteAsset, replay::{PlayerButton, PlayerInput}, sfx::{PlaySfx, Sfx}, shapes::{self, ShapeBloop}, trajectory};
 
-use super::{EzTextBundle, B_BOUND, L_BOUND, R_BOUND};
+use super::EzTextBundle;
 
 
 
 
-const PLAYER_T_BOUND: f32 = -200.;
-const SPAWN_X: f32 = 0.;
-const SPAWN_Y: f32 = B_BOUND + 100.;
-
 const MOVE_SPEED: f32 = 180.;
 const SHOT_DELAY: f32 = 0.08;
 
@@ -38,69 +34,64 @@ pub struct ScoreText;
 #[derive(Component)]
 pub struct TimeText;
 
+#[derive(Component)]
+pub struct GrazeText;
+
 
 pub fn sprite_movement(
     time: Res<Time>, 
     mut sprite_position: Query<(Entity, &mut Transform, &mut gun::Gun, &mut shapes::ShapeGun), With<PlayerControlled>>,
-    keycode: Res<ButtonInput<KeyCode>>,
+    input: Res<PlayerInput>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut game_state: ResMut<NextState<GameState>>,
-    mut score_board: ResMut<ScoreBoard>,
-    game_time: Res<GameTimer>
+    mut pool: ResMut<BulletPool>,
+    mut sfx: EventWriter<PlaySfx>,
+    playfield: Res<Playfield>
 ) {
     
     if let Ok((_, mut transform , mut gun, mut s_gun)) = sprite_position.get_single_mut() {
         gun.tick_time(time.delta());
         
 
-        //gizmos.rect_2d(transform.translation.truncate(), 0., Vec2::new(32., 32.), Color::rgb(1.,1.,0.));
 
         // Bound X
-        if transform.translation.x > R_BOUND as f32 {
-            transform.translation.x  = 1. - L_BOUND as f32 ;
-        } else if transform.translation.x < -(L_BOUND as f32) {
-            transform.translation.x = (R_BOUND - 1) as f32;
+        if let Some(x) = playfield.wrap_x(transform.translation.x) {
+            transform.translation.x = x;
         }
         // Bound Y 
-        if transform.translation.y > PLAYER_T_BOUND as f32 {
-            transform.translation.y  = (PLAYER_T_BOUND - 1.) as f32;
-        } else if transform.translation.y < SPAWN_Y {
-            transform.translation.y = SPAWN_Y + 1.; 
+        if transform.translation.y > playfield.player_top {
+            transform.translation.y = playfield.player_top - 1.;
+        } else if transform.translation.y < playfield.player_bottom() {
+            transform.translation.y = playfield.player_bottom() + 1.; 
         }
 
 
     
-        let speed_mult = if keycode.pressed(KeyCode::ShiftLeft){ 3. } else { 1.}; // Speed boost
+        let speed_mult = if input.held(PlayerButton::Boost){ 3. } else { 1.}; // Speed boost
         let move_dist = MOVE_SPEED * time.delta_seconds() * speed_mult;
         //Move Left
-        if keycode.pressed(KeyCode::KeyA) {
+        if input.held(PlayerButton::Left) {
             transform.translation.x -= move_dist; // if transform.translation.x - move_dist < -500. {0.} else {move_dist}
         }
         // Move Right
-        if keycode.pressed(KeyCode::KeyD)  {
+        if input.held(PlayerButton::Right)  {
             transform.translation.x +=  move_dist;//if transform.translation.x + move_dist > R_BOUND as f32 {0.} else {move_dist}
         }
     
-        if keycode.pressed(KeyCode::KeyW){ 
+        if input.held(PlayerButton::Up){ 
             transform.translation.y += move_dist;
         }
-        if keycode.pressed(KeyCode::KeyS){
+        if input.held(PlayerButton::Down){
             transform.translation.y -= move_dist;
         }
 
-        if keycode.just_pressed(KeyCode::KeyR) {
+        if input.just_pressed(PlayerButton::Reload) {
             gun.set_ammo(0);
             gun.reload_stopwatch.reset();
             gun.reload_stopwatch.tick(Duration::from_secs_f32(1.5));
         }
     
-        if keycode.just_pressed(KeyCode::KeyE) && s_gun.get_shots() > 0 {
-            commands.spawn(AudioBundle {
-                source: asset_server.load("sounds/womp.wav"),
-                // auto-despawn the entity when playback finishes
-                settings: PlaybackSettings::DESPAWN
-            });
+        if input.just_pressed(PlayerButton::Special) && s_gun.get_shots() > 0 {
+            sfx.send(PlaySfx(Sfx::Womp));
             
             s_gun.shoot();
             let px = transform.translation.x;
@@ -110,7 +101,7 @@ pub fn sprite_movement(
             let bloop = &s_gun.bullet;
     
             for p in points {  
-                commands.spawn(bullet::BulletBundle::new(p.0, p.1, bullet::Bullet::new(bloop.0, bloop.1, bloop.2, bloop.3, bloop.4, bloop.5), asset_server.load("plasma_green.png")));
+                pool.spawn(&mut commands, p.0, p.1, bullet::Bullet::new(bloop.0, bloop.1.clone(), bloop.2, bloop.3, bloop.4), Plasma::Green);
             }
                 
             
@@ -119,22 +110,18 @@ pub fn sprite_movement(
 
        
         // Shoot 
-        if keycode.pressed(KeyCode::Space) && gun.can_shoot() {
+        if input.held(PlayerButton::Fire) && gun.can_shoot() {
             gun.reset_shot_timer();
             gun.shoot_bullet();
 
-            commands.spawn(AudioBundle {
-                source: asset_server.load("sounds/laser.wav"),
-                // auto-despawn the entity when playback finishes
-                settings: PlaybackSettings::DESPAWN
-            });
+            sfx.send(PlaySfx(Sfx::Laser));
 
             
 
             let bullets = gun.get_bullets();
 
             for bul in bullets {
-                commands.spawn(bullet::BulletBundle::new(transform.translation.x, transform.translation.y, bullet::Bullet::new(bul.0, bul.1, bul.2, bul.3, bul.4, gun.get_bullet_damage()), asset_server.load("plasma_blue.png")));
+                pool.spawn(&mut commands, transform.translation.x, transform.translation.y, bullet::Bullet::new(bul.0, bul.1.clone(), bul.2, bul.3, gun.get_bullet_damage()), Plasma::Blue);
             }
 
             /* commands.spawn(bullet::BulletBundle::new(transform.translation.x, transform.translation.y, bullet::Bullet::new( 1, |_| 3., |a: f32| 5.*(a).cos()  ,  0.,  true, bullet_damage), asset_server.load("plasma_blue.png")));
@@ -158,11 +145,6 @@ pub fn sprite_movement(
             }
         }
     }
-    else{
-        score_board.set_game_time(game_time.0.elapsed_secs());
-        
-        game_state.set(GameState::Menu); // restart game when player is unfindable (dead)
-    }
 }
 
 
@@ -170,8 +152,11 @@ pub fn sprite_movement(
 
 #[derive(Bundle)]
 pub struct PlayerBundle {
-    sprite_bundle: SpriteBundle,
+    spatial_bundle: SpatialBundle,
+    sprite: SpriteAsset,
     control: PlayerControlled,
+    hitbox: Hitbox,
+    bombs: Bombs,
     health: health::Health,
     gun: gun::Gun,
     s_gun: shapes::ShapeGun
@@ -179,24 +164,23 @@ pub struct PlayerBundle {
 }
 
 impl PlayerBundle {
-    fn new(asset: Handle<Image>) -> PlayerBundle {
+    fn new(spawn: Vec2) -> PlayerBundle {
         let mut starting_bullets = Vec::new();
-        starting_bullets.push(gun::BulletBlueprint(1,|_| 5., |x| 9.*(x*10.).cos(), 0., true, 50));
-        starting_bullets.push(gun::BulletBlueprint(1,|_| 5., |x| -9.*(x*10.).cos(), 0., true, 50));
-        starting_bullets.push(gun::BulletBlueprint(1,|_| 20., |_: f32| 0., 0., true, 50));
+        starting_bullets.push(gun::BulletBlueprint(1, trajectory::path("9*cos(t*10)", "5"), 0., true, 50));
+        starting_bullets.push(gun::BulletBlueprint(1, trajectory::path("-9*cos(t*10)", "5"), 0., true, 50));
+        starting_bullets.push(gun::BulletBlueprint(1, trajectory::path("0", "20"), 0., true, 50));
         
         let mut s_gun = shapes::ShapeGun::default();
         s_gun.add_bloop(ShapeBloop{ offset: (0., 120.), num_bullets: 50, t: shapes::ShapeType::Circle, size_scale: (1.6, 1.6)});
 
-        s_gun.bullet = gun::BulletBlueprint(1, |y| y*y, |x| 5. * (x*5.).cos(), 0., true, 60);
+        s_gun.bullet = gun::BulletBlueprint(1, trajectory::path("5*cos(t*5)", "t*t"), 0., true, 60);
 
         PlayerBundle {
-            sprite_bundle: SpriteBundle {
-                texture: asset,
-                transform: Transform::from_xyz(SPAWN_X, SPAWN_Y, 1.),
-                ..default()
-            },
+            spatial_bundle: SpatialBundle::from_transform(Transform::from_xyz(spawn.x, spawn.y, 1.)),
+            sprite: SpriteAsset::new("player.png"),
             control: PlayerControlled,
+            hitbox: Hitbox::player(),
+            bombs: Bombs::default(),
             health: health::Health::new(SHIELD_SIZE, HEALTH_SIZE, 3.75, 15),
             gun: gun::Gun::new(starting_bullets, SHOT_DELAY, BULLET_DAMAGE, 10, 50, 3.0),
             s_gun: s_gun
@@ -204,26 +188,20 @@ impl PlayerBundle {
     } 
 }
 
-pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>){
-    
-    let asset = asset_server.load("player.png");
-    
+pub fn spawn_player(mut commands: Commands, playfield: Res<Playfield>){
     commands.spawn(
-        PlayerBundle::new(asset)
+        PlayerBundle::new(playfield.player_spawn())
     );
+}
 
+/// Spawn the score / time / graze text (the bars and icons are in `hud`)
+pub fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>){
 
     commands.spawn(EzTextBundle::new(String::from(""), 40., 40., 20., asset_server.load("fonts/EvilEmpire.otf"), Color::GOLD,ScoreText));
     commands.spawn(EzTextBundle::new(String::from("00:00"), 40., 40., 940., asset_server.load("fonts/EvilEmpire.otf"), Color::GOLD,TimeText));
-    commands.spawn(EzTextBundle::new(String::from("Ammo"), 40., 880., 900., asset_server.load("fonts/EvilEmpire.otf"), Color::WHITE,AmmoLabel));
-    commands.spawn(EzTextBundle::new(String::from("Health"), 40., 880., 20., asset_server.load("fonts/EvilEmpire.otf"), Color::GREEN,HealthLabel));
-    
+    commands.spawn(EzTextBundle::new(String::from(""), 28., 85., 20., asset_server.load("fonts/EvilEmpire.otf"), Color::ORANGE,GrazeText));
 
 }
-#[derive(Component)]
-pub struct AmmoLabel;
-#[derive(Component)]
-pub struct HealthLabel;
 
 
 pub fn update_player_score(
@@ -231,7 +209,17 @@ pub fn update_player_score(
     scoreboard: Res<game::ScoreBoard>
 ) {
     for mut text in &mut query {
-        text.sections[0].value = format!("{:09} x {:01}", scoreboard.get_score(), scoreboard.get_mul())
+        text.sections[0].value = format!("{:09}", scoreboard.get_score())
+    }
+}
+
+
+pub fn update_graze_display(
+    mut query: Query<&mut Text, With<GrazeText>>,
+    scoreboard: Res<game::ScoreBoard>
+) {
+    for mut text in &mut query {
+        text.sections[0].value = format!("GRAZE {}", scoreboard.get_grazes())
     }
 }
 
@@ -247,26 +235,3 @@ pub fn update_time_display(
         text.sections[0].value = format!("{:02}:{:02}", minutes, seconds);
     }
 }
-
-pub fn update_health_display(
-    health: Query<&health::Health, With<PlayerControlled>>,
-    mut text_query: Query<&mut Text, With<HealthLabel>>
-){
-    if let Ok(health) = health.get_single() {
-        for mut text in &mut text_query {
-            text.sections[0].value = format!("HP/SHIELD\n{:03}/{:03}", health.get_health(), health.get_shield())
-        }
-    }
-}
-
-
-pub fn update_ammo_display(
-    guns_query: Query<(&gun::Gun, &shapes::ShapeGun), With<PlayerControlled>>,
-    mut text_query: Query<&mut Text, With<AmmoLabel>>
-){
-    if let Ok((gun, shape)) = guns_query.get_single() {
-        for mut text in &mut text_query {
-            text.sections[0].value = format!("AMMO\n{:03} : {:02} ", gun.get_ammo(), shape.get_shots())
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,77 @@
+use bevy::{prelude::*, render::camera::{ScalingMode, Viewport}, window::{PrimaryWindow, WindowResized}};
+
+/// Size of the area the game camera shows, in world units (what the window used to be locked to)
+pub const VIEW_WIDTH: f32 = 1040.;
+pub const VIEW_HEIGHT: f32 = 960.;
+
+/// The arena in world units: where things wrap, despawn and come in from, whatever size the window is
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Playfield {
+    /// Enemies come in from above this and only start shooting below it
+    pub top: f32,
+    /// Enemies wrap back to the top below this, bullets and power ups are gone
+    pub bottom: f32,
+    /// Enemies, player bullets and the player wrap across from one side to the other
+    pub left: f32,
+    pub right: f32,
+    /// Highest the player can fly
+    pub player_top: f32,
+}
+
+impl Default for Playfield {
+    fn default() -> Self {
+        Playfield { top: 400., bottom: -500., left: -500., right: 500., player_top: -200. }
+    }
+}
+
+impl Playfield {
+    /// Lowest the player can fly (and where they spawn)
+    pub fn player_bottom(&self) -> f32 { self.bottom + 100. }
+
+    pub fn player_spawn(&self) -> Vec2 { Vec2::new(0., self.player_bottom()) }
+
+    /// Where something at `x` comes back in if it went off either side
+    pub fn wrap_x(&self, x: f32) -> Option<f32> {
+        if x < self.left { Some(self.right - 1.) }
+        else if x > self.right { Some(self.left + 1.) }
+        else { None }
+    }
+}
+
+/// The game camera shows exactly the view, letterboxed to the middle of the window
+pub fn projection() -> OrthographicProjection {
+    OrthographicProjection {
+        scaling_mode: ScalingMode::Fixed { width: VIEW_WIDTH, height: VIEW_HEIGHT },
+        ..Camera2dBundle::default().projection
+    }
+}
+
+/// Fit the game camera's viewport to the window keeping the view's shape, and scale the UI with it so the HUD stays put on the view
+pub fn letterbox(
+    mut resized: EventReader<WindowResized>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut Camera>,
+    mut ui_scale: ResMut<UiScale>
+){
+    let resize = resized.read().count() > 0;
+    let Ok(window) = window.get_single() else { return; };
+    // minimised, nothing to fit (and a zero scale would blow up the UI)
+    if window.physical_width() == 0 || window.physical_height() == 0 { return; }
+
+    let size = Vec2::new(window.physical_width() as f32, window.physical_height() as f32);
+    let scale = (size.x / VIEW_WIDTH).min(size.y / VIEW_HEIGHT);
+    let fitted = (Vec2::new(VIEW_WIDTH, VIEW_HEIGHT) * scale).floor().max(Vec2::ONE);
+    let viewport = Viewport {
+        physical_position: ((size - fitted) / 2.).as_uvec2(),
+        physical_size: fitted.as_uvec2(),
+        ..default()
+    };
+
+    let ui = scale / window.scale_factor();
+    if ui_scale.0 != ui { ui_scale.0 = ui; }
+    for mut camera in &mut cameras {
+        if resize || camera.is_added() { // the menus spawn their own cameras
+            camera.viewport = Some(viewport.clone());
+        }
+    }
+}
@@ -1,11 +1,9 @@
-use bevy::{math::bounding::Aabb2d, prelude::*};
+use bevy::prelude::*;
 use rand::Rng;
 
-use super::B_BOUND;
+use crate::{bomb::Bombs, game::GameRng, gun::{self, Gun}, health::Health, hitbox::Hitbox, player::PlayerControlled, playfield::Playfield, render::SpriteAsset, shapes::{self, ShapeBloop, ShapeGun}, spatial::{Layer, SpatialGrid}, trajectory};
 
-use crate::{bullet, gun::{self, Gun}, health::Health, player::PlayerControlled, shapes::{self, ShapeBloop, ShapeGun}};
-
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, PartialEq, Eq)]
 pub enum PowerUpTypes { 
     BulletAmmo,
     BulletSpeed,
@@ -18,10 +16,11 @@ pub enum PowerUpTypes {
     HealthIncrease,
     ShieldIncrease,
     ShieldRegen,
+    Bomb,
 }
 
 impl PowerUpTypes {
-    fn value(&self) -> &str {
+    pub fn value(&self) -> &'static str {
         match self {
             PowerUpTypes::BulletAmmo => "power_ups/bullets/bullet_ammo.png",
             PowerUpTypes::BulletSpeed => "power_ups/bullets/bullet_speed.png",
@@ -34,32 +33,54 @@ impl PowerUpTypes {
             PowerUpTypes::HealthIncrease => "power_ups/health/health_increase.png",
             PowerUpTypes::ShieldIncrease => "power_ups/health/shield_increase.png",
             PowerUpTypes::ShieldRegen => "power_ups/health/shield_speed.png",
+            PowerUpTypes::Bomb => "power_ups/red_base.png",
             
         }
     }
 }
 
 
+/// Power ups picked up this run in the order they were first found, and how many of each
+#[derive(Resource, Default)]
+pub struct Inventory(Vec<(PowerUpTypes, u32)>);
+
+impl Inventory {
+    fn add(&mut self, power_up: PowerUpTypes) {
+        match self.0.iter_mut().find(|(p, _)| *p == power_up) {
+            Some((_, count)) => *count += 1,
+            None => self.0.push((power_up, 1)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(PowerUpTypes, u32)> { self.0.iter() }
+}
+
+pub fn reset_inventory(mut inventory: ResMut<Inventory>){
+    *inventory = Inventory::default();
+}
+
 #[derive(Bundle)]
 pub struct PowerUpBundle {
-    sprite: SpriteBundle,
+    spatial_bundle: SpatialBundle,
+    sprite: SpriteAsset,
+    hitbox: Hitbox,
     p_type: PowerUpTypes
 }
 
 
 /// Spawn 3 random power ups at the top of the screen 
-pub fn spawn_powerup_wave(coms: &mut Commands, assets: &Res<AssetServer>){
+pub fn spawn_powerup_wave(coms: &mut Commands, rng: &mut GameRng){
     // pick a random bullet powerup
     // a random shape power up,
     // and a random health power up 
 
     let bullet_pups = vec![PowerUpTypes::BulletAmmo, PowerUpTypes::BulletDamage, PowerUpTypes::BulletSpeed, PowerUpTypes::BulletAmmo, PowerUpTypes::BulletDamage, PowerUpTypes::BulletSpeed,  PowerUpTypes::AddRandomBullet];
     let shape_pups = vec![PowerUpTypes::ShapeAmmo, PowerUpTypes::ShapeReloadTime, PowerUpTypes::ShapeSize, PowerUpTypes::ShapeAmmo, PowerUpTypes::ShapeReloadTime, PowerUpTypes::ShapeSize, PowerUpTypes::AddRandomShape];
-    let health_pups = vec![PowerUpTypes::HealthIncrease, PowerUpTypes::ShieldIncrease, PowerUpTypes::ShieldRegen];
+    let health_pups = vec![PowerUpTypes::HealthIncrease, PowerUpTypes::ShieldIncrease, PowerUpTypes::ShieldRegen, PowerUpTypes::Bomb];
 
-    let bup = rand::thread_rng().gen_range(0..(bullet_pups.len()));
-    let sup = rand::thread_rng().gen_range(0..(shape_pups.len()));
-    let hup = rand::thread_rng().gen_range(0..(health_pups.len()));
+    let bup = rng.0.gen_range(0..(bullet_pups.len()));
+    let sup = rng.0.gen_range(0..(shape_pups.len()));
+    let hup = rng.0.gen_range(0..(health_pups.len()));
 
     let bup = bullet_pups[bup];
     let sup = shape_pups[sup];
@@ -69,14 +90,10 @@ pub fn spawn_powerup_wave(coms: &mut Commands, assets: &Res<AssetServer>){
     let pups = [bup, sup, hup];
 
     for n in 0..pups.len() {
-        let asset = pups[n];
-        let asset = asset.value().to_owned();
         coms.spawn(PowerUpBundle {
-            sprite: SpriteBundle {
-                texture: assets.load(asset),
-                transform: Transform::from_xyz(points[n].0, points[n].1, 1.0),
-                ..default()
-            },
+            spatial_bundle: SpatialBundle::from_transform(Transform::from_xyz(points[n].0, points[n].1, 1.0)),
+            sprite: SpriteAsset::new(pups[n].value()),
+            hitbox: Hitbox::power_up(),
             p_type: pups[n]
         });
     }
@@ -85,32 +102,43 @@ pub fn spawn_powerup_wave(coms: &mut Commands, assets: &Res<AssetServer>){
 const MOVE_SPEED: f32 = 180.;
 
 pub fn move_powerups(
-    mut power_ups: Query<(Entity, &mut Transform), With<PowerUpTypes>>,
+    mut power_ups: Query<(Entity, &mut Transform, &Hitbox), With<PowerUpTypes>>,
+    mut grid: ResMut<SpatialGrid>,
     mut coms: Commands,
-    time: Res<Time>
+    time: Res<Time>,
+    playfield: Res<Playfield>
 ){
-    for (ent, mut transform) in &mut power_ups{
-        if transform.translation.y < B_BOUND {
+    for (ent, mut transform, hitbox) in &mut power_ups{
+        let gone = transform.translation.y < playfield.bottom;
+        if gone {
             coms.entity(ent).despawn();
         }
 
         transform.translation.y -= MOVE_SPEED * time.delta_seconds();
+
+        if !gone {
+            grid.insert(ent, hitbox.layer, hitbox.volume(&transform));
+        }
     }
 }
 
 
 /// Move and collide power ups 
 pub fn handle_powerup_collision(
-    mut player: Query<(&mut Health, &mut Gun, &mut ShapeGun, &Transform), With<PlayerControlled>>,
-    power_ups: Query<(Entity, &PowerUpTypes, &Transform), With<PowerUpTypes>>,
-    mut coms: Commands
+    mut player: Query<(&mut Health, &mut Gun, &mut ShapeGun, &Transform, &Hitbox), With<PlayerControlled>>,
+    mut bombs: Query<&mut Bombs, With<PlayerControlled>>,
+    power_ups: Query<(Entity, &PowerUpTypes)>,
+    grid: Res<SpatialGrid>,
+    mut coms: Commands,
+    mut rng: ResMut<GameRng>,
+    mut inventory: ResMut<Inventory>
 ){
-    if let Ok((mut health, mut gun, mut shape_gun, p_transform)) = player.get_single_mut() {
+    if let Ok((mut health, mut gun, mut shape_gun, p_transform, p_hitbox)) = player.get_single_mut() {
+        if !p_hitbox.mask.contains(Layer::PowerUp) { return; }
         let mut did_contact = false;
-        for (ent, power_up, transform) in &power_ups {
+        for (ent, _) in grid.query(Layer::PowerUp, p_hitbox.volume(p_transform)) {
             if did_contact {break;}
-            let collision = bullet::bullet_collision(Aabb2d::new(p_transform.translation.truncate(), Vec2::new(16.,16.)), Aabb2d::new(transform.translation.truncate(), Vec2::new(16.,16.)));
-            if let Some(_) = collision {
+            if let Ok((_, power_up)) = power_ups.get(ent) {
                 match power_up {
                     PowerUpTypes::BulletAmmo => {
                         let cur = gun.get_max_ammo();
@@ -125,13 +153,13 @@ pub fn handle_powerup_collision(
                         gun.set_bullet_damage(cur + 50);
                     },
                     PowerUpTypes::AddRandomBullet => {
-                        let b_choice = rand::thread_rng().gen_range(0..5);
+                        let b_choice = rng.0.gen_range(0..5);
                         match b_choice {
-                            0 => gun.add_bullet(gun::BulletBlueprint(1, |y| y*y, |_| 0., 0., true, 50)),
-                            1 => gun.add_bullet(gun::BulletBlueprint(1, |y| y*y, |_| 5., 0., true, 50)),
-                            2 => gun.add_bullet(gun::BulletBlueprint(1, |y| y*y, |_| -5., 0., true, 50)),
-                            3 => gun.add_bullet(gun::BulletBlueprint(1, |_| 10., |_| 5., 0., true, 50)),
-                            _ => gun.add_bullet(gun::BulletBlueprint(1, |_| 10., |_| -5., 0., true, 50))
+                            0 => gun.add_bullet(gun::BulletBlueprint(1, trajectory::path("0", "t*t"), 0., true, 50)),
+                            1 => gun.add_bullet(gun::BulletBlueprint(1, trajectory::path("5", "t*t"), 0., true, 50)),
+                            2 => gun.add_bullet(gun::BulletBlueprint(1, trajectory::path("-5", "t*t"), 0., true, 50)),
+                            3 => gun.add_bullet(gun::BulletBlueprint(1, trajectory::path("5", "10"), 0., true, 50)),
+                            _ => gun.add_bullet(gun::BulletBlueprint(1, trajectory::path("-5", "10"), 0., true, 50))
                         }
                     },
                     PowerUpTypes::ShapeAmmo => {
@@ -143,14 +171,14 @@ pub fn handle_powerup_collision(
                         shape_gun.set_reload_time(cur - 0.05);
                     },
                     PowerUpTypes::AddRandomShape => {
-                        let s_choice = rand::thread_rng().gen_range(0..3);
-                        let x_off = rand::thread_rng().gen_range(-200. .. 200.);
-                        let y_off = rand::thread_rng().gen_range(0. .. 150.);
+                        let s_choice = rng.0.gen_range(0..3);
+                        let x_off = rng.0.gen_range(-200. .. 200.);
+                        let y_off = rng.0.gen_range(0. .. 150.);
 
                         let offset = (x_off, y_off);
                         
-                        let x_scale = rand::thread_rng().gen_range(0.5 .. 3.0);
-                        let y_scale = rand::thread_rng().gen_range(0.5 .. 3.0);
+                        let x_scale = rng.0.gen_range(0.5 .. 3.0);
+                        let y_scale = rng.0.gen_range(0.5 .. 3.0);
                         let scale = (x_scale, y_scale);
                         
                         match s_choice{
@@ -181,8 +209,14 @@ pub fn handle_powerup_collision(
                         let cur = health.get_recharge();
                         health.set_recharge(cur + 50);
                     },
+                    PowerUpTypes::Bomb => {
+                        for mut bombs in &mut bombs {
+                            bombs.add(1);
+                        }
+                    },
                     
                 }
+                inventory.add(*power_up);
             
                 coms.entity(ent).despawn();
                 did_contact = true;
@@ -190,7 +224,7 @@ pub fn handle_powerup_collision(
         }
 
         if did_contact {
-            for (e, _, _) in &power_ups {
+            for (e, _) in &power_ups {
                 coms.entity(e).despawn();
             }
         }
@@ -0,0 +1,166 @@
+use std::borrow::Cow;
+
+use bevy::{core_pipeline::tonemapping::Tonemapping, prelude::*};
+use bevy_hanabi::prelude::*;
+
+use crate::{bomb::{self, BombBlast}, boss, bullet::Plasma, campaign, explosion, hitbox::{self, HitboxDebug}, hud, lives::PlayerDown, player, playfield, settings::Settings, sfx};
+use super::GameState;
+
+/// Everything that only matters when there is a window to look at (sprites, camera, hud, particles, sound)
+pub struct BulletHellRender;
+
+/// Image an entity should be drawn with, the simulation only tags entities and the renderer loads the texture
+#[derive(Component, Clone)]
+pub struct SpriteAsset(pub Cow<'static, str>);
+
+impl SpriteAsset {
+    pub fn new(path: impl Into<Cow<'static, str>>) -> SpriteAsset {
+        SpriteAsset(path.into())
+    }
+}
+
+/// Plasma textures loaded up front so firing never has to go through the asset server
+#[derive(Resource)]
+pub struct PlasmaTextures([Handle<Image>; 4]);
+
+impl PlasmaTextures {
+    pub fn get(&self, plasma: Plasma) -> Handle<Image> {
+        self.0[plasma as usize].clone()
+    }
+}
+
+#[derive(Component)]
+pub struct MovingBackground(f32);
+
+const SCROLL_SPEED: f32 = 0.25;
+
+/// How shaken up the camera is (0 to 1), bombs and losing a life add to it
+#[derive(Resource, Default)]
+pub struct ScreenShake(f32);
+
+/// Shake lost per second
+const SHAKE_DECAY: f32 = 1.5;
+/// Furthest the camera is thrown (in pixels) at full shake
+const MAX_SHAKE: f32 = 18.;
+
+impl Plugin for BulletHellRender {
+    fn build(&self, app: &mut App){
+        app
+            .add_plugins(HanabiPlugin)
+            .add_plugins(sfx::make_plugin)
+            .init_resource::<HitboxDebug>()
+            .init_resource::<ScreenShake>()
+            .add_systems(Startup, load_plasma_textures)
+            .add_systems(Update, playfield::letterbox)
+            .add_systems(OnEnter(GameState::Game), (setup, player::spawn_hud, hud::spawn_hud, boss::spawn_boss_bar, campaign::spawn_level_banner, explosion::setup))
+            .add_systems(Update, (attach_sprites, attach_bullet_sprites, explosion::spawn_explosions).run_if(in_state(GameState::Game)))
+            .add_systems(Update, (hitbox::toggle_hitbox_debug, hitbox::draw_hitboxes).chain().run_if(in_state(GameState::Game)))
+            .add_systems(Update, (bomb::blink_invulnerable, shake_camera).run_if(in_state(GameState::Game)))
+            .add_systems(FixedUpdate, (
+                player::update_player_score,
+                player::update_time_display,
+                player::update_graze_display,
+                hud::update_meters,
+                hud::update_icons,
+                hud::update_wave,
+                hud::update_inventory,
+                boss::update_boss_bar,
+                campaign::update_level_banner,
+                move_background_image
+                ).run_if(in_state(GameState::Game)));
+    }
+}
+
+/// Setup the camera and background
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<Settings>, mut shake: ResMut<ScreenShake>) {
+
+    let mut camera = commands.spawn( //Camera with bloom settings (unless turned off)
+        Camera2dBundle {
+            camera: Camera {
+                hdr: true,
+                ..default()
+            },
+            tonemapping: Tonemapping::TonyMcMapface,
+            projection: playfield::projection(),
+            ..default()
+        }
+    );
+    if let Some(bloom) = settings.bloom_settings() {
+        camera.insert(bloom);
+    }
+    shake.0 = 0.;
+
+    commands.spawn(
+        (
+            SpriteBundle {
+            texture: asset_server.load("bckg.png"),
+            transform: Transform {
+                translation: Vec3::new(0.,0., -1.0),
+                scale: Vec3::new(0.6, 0.6, 1.0),
+                ..default()
+            },
+            ..default()
+            },
+            MovingBackground(1.0)
+        ));
+}
+
+/// Give newly tagged (or re-tagged) entities their sprite
+fn attach_sprites(
+    query: Query<(Entity, &SpriteAsset), Changed<SpriteAsset>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+){
+    for (ent, sprite) in &query {
+        commands.entity(ent).try_insert((Sprite::default(), asset_server.load::<Image>(sprite.0.to_string())));
+    }
+}
+
+fn load_plasma_textures(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands.insert_resource(PlasmaTextures(Plasma::ALL.map(|plasma| asset_server.load(plasma.asset()))));
+}
+
+/// Give bullets the texture for their plasma (again when a pooled bullet is fired with a different one)
+fn attach_bullet_sprites(
+    query: Query<(Entity, &Plasma), Changed<Plasma>>,
+    mut commands: Commands,
+    textures: Res<PlasmaTextures>
+){
+    for (ent, plasma) in &query {
+        commands.entity(ent).try_insert((Sprite::default(), textures.get(*plasma)));
+    }
+}
+
+pub fn move_background_image(
+    mut bckg: Query<(&mut Transform, &mut MovingBackground), With<MovingBackground>>
+){
+    if let Ok((mut transform, mut back)) = bckg.get_single_mut() {
+        if transform.translation.y < -400. || transform.translation.y > 300. {back.0 = back.0 * -1.0}
+        transform.translation.y += SCROLL_SPEED * back.0;
+
+    }
+}
+
+/// Throw the camera around a little after bombs and deaths (unless screen shake is turned off)
+fn shake_camera(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut shake: ResMut<ScreenShake>,
+    mut blasts: EventReader<BombBlast>,
+    mut downs: EventReader<PlayerDown>,
+    mut camera: Query<&mut Transform, With<Camera>>
+){
+    if time.delta_seconds() == 0. { return; } // paused
+
+    shake.0 += blasts.read().count() as f32 * 0.6 + downs.read().count() as f32 * 0.8;
+    shake.0 = (shake.0 - SHAKE_DECAY * time.delta_seconds()).clamp(0., 1.);
+
+    let offset = match settings.screen_shake {
+        true => MAX_SHAKE * shake.0 * shake.0 * Vec2::new(rand::random::<f32>() * 2. - 1., rand::random::<f32>() * 2. - 1.),
+        false => Vec2::ZERO,
+    };
+    for mut transform in &mut camera {
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}
@@ -0,0 +1,378 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+
+use crate::{controls::{Action, ActionState}, game::{self, Difficulty, GameMode, GameTimer, RunSeed, ScoreBoard}, storage, GameState};
+
+const MAGIC: &[u8; 4] = b"BHR4";
+/// Where each run is saved without `--record`, under the data dir next to the leaderboard and settings
+const LAST_REPLAY_FILE: &str = "replays/last.bhr";
+
+#[derive(Clone, Copy)]
+/// Buttons the player can hold during a fixed tick (bit flags in a replay)
+pub enum PlayerButton {
+    Left,
+    Right,
+    Up,
+    Down,
+    Boost,
+    Fire,
+    Special,
+    Reload,
+    Bomb,
+}
+
+impl PlayerButton {
+    fn bit(&self) -> u16 {
+        1 << (*self as u8)
+    }
+}
+
+/// What the player is pressing this fixed tick, the simulation reads this instead of the keyboard
+#[derive(Resource, Default)]
+pub struct PlayerInput { held: u16, pressed: u16 }
+
+impl PlayerInput {
+    pub fn held(&self, button: PlayerButton) -> bool {
+        self.held & button.bit() != 0
+    }
+
+    /// Held this tick but not the one before
+    pub fn just_pressed(&self, button: PlayerButton) -> bool {
+        self.pressed & button.bit() != 0
+    }
+
+    fn advance(&mut self, held: u16) {
+        self.pressed = held & !self.held;
+        self.held = held;
+    }
+}
+
+/// A recorded run: the seed and mode, the buttons held every fixed tick and how the run ended
+#[derive(Default, Clone)]
+pub struct Replay {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+    pub score: u64,
+    pub waves: u64,
+    pub game_time: f32,
+    pub inputs: Vec<u16>,
+}
+
+impl Replay {
+    /// Write the replay run length encoded (inputs barely change tick to tick)
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.push(match self.mode { GameMode::Endless => 0, GameMode::Campaign => 1 });
+        bytes.push(match self.difficulty { Difficulty::Easy => 0, Difficulty::Normal => 1, Difficulty::Hard => 2 });
+        bytes.extend_from_slice(&self.score.to_le_bytes());
+        bytes.extend_from_slice(&self.waves.to_le_bytes());
+        bytes.extend_from_slice(&self.game_time.to_le_bytes());
+
+        let mut runs: Vec<(u16, u16)> = Vec::new();
+        for input in &self.inputs {
+            match runs.last_mut() {
+                Some((held, len)) if *held == *input && *len < u16::MAX => *len += 1,
+                _ => runs.push((*input, 1)),
+            }
+        }
+
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for (held, len) in runs {
+            bytes.extend_from_slice(&held.to_le_bytes());
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, bytes)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let bytes = fs::read(path)?;
+        let mut reader = ByteReader { bytes: &bytes, at: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a replay file"));
+        }
+
+        let mut replay = Replay {
+            seed: u64::from_le_bytes(reader.array()?),
+            mode: if reader.take(1)?[0] == 0 { GameMode::Endless } else { GameMode::Campaign },
+            difficulty: match reader.take(1)?[0] { 0 => Difficulty::Easy, 2 => Difficulty::Hard, _ => Difficulty::Normal },
+            score: u64::from_le_bytes(reader.array()?),
+            waves: u64::from_le_bytes(reader.array()?),
+            game_time: f32::from_le_bytes(reader.array()?),
+            inputs: Vec::new(),
+        };
+
+        let runs = u32::from_le_bytes(reader.array()?);
+        for _ in 0..runs {
+            let held = u16::from_le_bytes(reader.array()?);
+            let len = u16::from_le_bytes(reader.array()?);
+            replay.inputs.extend(std::iter::repeat_n(held, len as usize));
+        }
+
+        Ok(replay)
+    }
+}
+
+struct ByteReader<'a> { bytes: &'a [u8], at: usize }
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let slice = self.bytes.get(self.at..self.at + n).ok_or(io::Error::new(io::ErrorKind::UnexpectedEof, "replay file is truncated"))?;
+        self.at += n;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+}
+
+/// Where input comes from this run
+#[derive(Resource)]
+pub enum InputSource {
+    Keyboard,
+    Playback { replay: Replay, cursor: usize },
+}
+
+/// Inputs of the current run, saved when the run ends (played back runs are only saved when asked to)
+#[derive(Resource, Default)]
+pub struct Recorder { replay: Replay, path: Option<PathBuf> }
+
+/// Did the last played back run end the same way as the recording
+#[derive(Resource, Default, PartialEq, Debug)]
+pub enum ReplayVerdict {
+    #[default]
+    None,
+    Matched,
+    Diverged,
+}
+
+pub struct ReplayPlugin {
+    /// Replay to play back instead of reading the keyboard
+    pub replay: Option<PathBuf>,
+    /// Where to write the recording of each run
+    pub record: Option<PathBuf>,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App){
+        let source = match &self.replay {
+            Some(path) => match Replay::load(path) {
+                Ok(replay) => InputSource::Playback { replay, cursor: 0 },
+                Err(e) => {
+                    error!("could not load replay {}: {}", path.display(), e);
+                    InputSource::Keyboard
+                }
+            },
+            None => InputSource::Keyboard,
+        };
+
+        app
+            .insert_resource(source)
+            .insert_resource(Recorder { path: self.record.clone(), ..default() })
+            .init_resource::<PlayerInput>()
+            .init_resource::<ActionState>() // stays empty without the controls plugin (headless)
+            .init_resource::<ReplayVerdict>()
+            .add_systems(OnEnter(GameState::Game), start_run.before(game::setup))
+            .add_systems(FixedPreUpdate, sample_input.run_if(game::run_active))
+            .add_systems(FixedPostUpdate, end_of_replay.run_if(game::run_active))
+            .add_systems(OnExit(GameState::Game), finish_run);
+    }
+}
+
+/// Reset recording / playback, a played back run is pinned to the seed, mode and difficulty it was recorded with
+fn start_run(
+    mut source: ResMut<InputSource>,
+    mut recorder: ResMut<Recorder>,
+    mut input: ResMut<PlayerInput>,
+    mut run_seed: ResMut<RunSeed>,
+    mut mode: ResMut<GameMode>,
+    mut difficulty: ResMut<Difficulty>
+){
+    *input = PlayerInput::default();
+    recorder.replay = Replay::default();
+
+    if let InputSource::Playback { replay, cursor } = source.as_mut() {
+        *cursor = 0;
+        run_seed.seed = replay.seed;
+        run_seed.pinned = true;
+        *mode = replay.mode;
+        *difficulty = replay.difficulty;
+    }
+    recorder.replay.mode = *mode;
+    recorder.replay.difficulty = *difficulty;
+}
+
+/// Read this tick's buttons from the player's controls (or the replay) and record them
+fn sample_input(
+    actions: Res<ActionState>,
+    mut source: ResMut<InputSource>,
+    mut input: ResMut<PlayerInput>,
+    mut recorder: ResMut<Recorder>,
+    run_seed: Res<RunSeed>
+){
+    let held = match source.as_mut() {
+        InputSource::Keyboard => {
+            let mut held = 0;
+            for (action, button) in [
+                (Action::Left, PlayerButton::Left),
+                (Action::Right, PlayerButton::Right),
+                (Action::Up, PlayerButton::Up),
+                (Action::Down, PlayerButton::Down),
+                (Action::Boost, PlayerButton::Boost),
+                (Action::Fire, PlayerButton::Fire),
+                (Action::Special, PlayerButton::Special),
+                (Action::Reload, PlayerButton::Reload),
+                (Action::Bomb, PlayerButton::Bomb),
+            ] {
+                if actions.pressed(action) { held |= button.bit(); }
+            }
+            held
+        },
+        InputSource::Playback { replay, cursor } => {
+            let held = replay.inputs.get(*cursor).copied().unwrap_or(0);
+            *cursor += 1;
+            held
+        }
+    };
+
+    recorder.replay.seed = run_seed.seed;
+    recorder.replay.inputs.push(held);
+    input.advance(held);
+}
+
+/// End a played back run on the tick its recording ended
+fn end_of_replay(
+    source: Res<InputSource>,
+    game_time: Res<GameTimer>,
+    mut score_board: ResMut<ScoreBoard>,
+    mut game_state: ResMut<NextState<GameState>>
+){
+    if let InputSource::Playback { replay, cursor } = source.as_ref() {
+        if *cursor >= replay.inputs.len() {
+            score_board.set_game_time(game_time.0.elapsed_secs());
+            game_state.set(GameState::Menu);
+        }
+    }
+}
+
+/// Save the recording and check a played back run against its recording
+fn finish_run(
+    mut recorder: ResMut<Recorder>,
+    source: Res<InputSource>,
+    score_board: Res<ScoreBoard>,
+    mut verdict: ResMut<ReplayVerdict>
+){
+    recorder.replay.score = score_board.get_score();
+    recorder.replay.waves = score_board.get_waves();
+    recorder.replay.game_time = score_board.get_game_time();
+
+    let save_path = match source.as_ref() {
+        InputSource::Keyboard => Some(recorder.path.clone().unwrap_or_else(|| storage::data_dir().join(LAST_REPLAY_FILE))),
+        InputSource::Playback { .. } => recorder.path.clone(),
+    };
+
+    if let Some(path) = save_path {
+        match recorder.replay.save(&path) {
+            Ok(_) => info!("saved replay to {}", path.display()),
+            Err(e) => warn!("could not save replay to {}: {}", path.display(), e),
+        }
+    }
+
+    if let InputSource::Playback { replay, .. } = source.as_ref() {
+        let got = &recorder.replay;
+        *verdict = if got.score == replay.score && got.waves == replay.waves && got.game_time == replay.game_time {
+            info!("replay matched: score {} waves {} time {:.2}s", got.score, got.waves, got.game_time);
+            ReplayVerdict::Matched
+        } else {
+            warn!(
+                "replay diverged: expected score {} waves {} time {:.2}s, got score {} waves {} time {:.2}s",
+                replay.score, replay.waves, replay.game_time, got.score, got.waves, got.game_time
+            );
+            ReplayVerdict::Diverged
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("bhr_test_{}_{}", std::process::id(), name))
+    }
+
+    /// A replay header written by hand
+    fn header(magic: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.extend_from_slice(&42u64.to_le_bytes());
+        bytes.extend([0, 1]);
+        bytes.extend_from_slice(&1234u64.to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(&12.5f32.to_le_bytes());
+        bytes
+    }
+
+    fn load_bytes(name: &str, bytes: &[u8]) -> io::Result<Replay> {
+        let path = temp_path(name);
+        fs::write(&path, bytes).unwrap();
+        let replay = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        replay
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut inputs = vec![0; 5];
+        inputs.extend([PlayerButton::Fire.bit() | PlayerButton::Bomb.bit(); 3]);
+        inputs.extend([PlayerButton::Left.bit(), 0, PlayerButton::Left.bit()]);
+        let replay = Replay { seed: 99, mode: GameMode::Campaign, difficulty: Difficulty::Hard, score: 5000, waves: 4, game_time: 61.25, inputs };
+
+        let path = temp_path("round_trip.bhr");
+        replay.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[..4], MAGIC);
+        // 5 runs of (u16 buttons, u16 length) after the 34 byte header and the run count
+        assert_eq!(bytes.len(), 34 + 4 + 5 * 4);
+        assert_eq!((loaded.seed, loaded.mode, loaded.difficulty), (99, GameMode::Campaign, Difficulty::Hard));
+        assert_eq!((loaded.score, loaded.waves, loaded.game_time), (5000, 4, 61.25));
+        assert_eq!(loaded.inputs, replay.inputs);
+    }
+
+    #[test]
+    fn long_runs_are_split() {
+        let replay = Replay { inputs: vec![7; u16::MAX as usize + 10], ..default() };
+
+        let path = temp_path("long_runs.bhr");
+        replay.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(u32::from_le_bytes(bytes[34..38].try_into().unwrap()), 2);
+        assert_eq!(loaded.inputs, replay.inputs);
+    }
+
+    #[test]
+    fn bad_files() {
+        assert_eq!(load_bytes("old", &header(b"BHR3")).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+        assert_eq!(load_bytes("magic", b"BHR9 and then some").err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+
+        let mut truncated = header(MAGIC);
+        truncated.extend_from_slice(&3u32.to_le_bytes());
+        assert_eq!(load_bytes("truncated", &truncated).err().map(|e| e.kind()), Some(io::ErrorKind::UnexpectedEof));
+    }
+}
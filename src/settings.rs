@@ -0,0 +1,242 @@
+use bevy::{core_pipeline::bloom::BloomSettings, prelude::*, window::{PresentMode, PrimaryWindow, WindowMode}};
+use serde::{Deserialize, Serialize};
+
+use crate::{game::Difficulty, menu::{MenuState, MenuView}, storage};
+
+const SETTINGS_FILE: &str = "settings.ron";
+const VOLUME_STEP: f32 = 0.1;
+const BLOOM_STEP: f32 = 0.05;
+const MAX_BLOOM: f32 = 0.5;
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+/// Audio, video and gameplay options, saved in `settings.ron` in the data dir
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub bloom: bool,
+    pub bloom_intensity: f32,
+    pub screen_shake: bool,
+    pub difficulty: Difficulty,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.,
+            music_volume: 1.,
+            sfx_volume: 1.,
+            fullscreen: false,
+            vsync: true,
+            bloom: true,
+            bloom_intensity: BloomSettings::default().intensity,
+            screen_shake: true,
+            difficulty: Difficulty::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Saved settings, or the defaults when there are none
+    pub fn load() -> Settings {
+        storage::load_ron(SETTINGS_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        storage::save_ron(SETTINGS_FILE, self);
+    }
+
+    /// Volume the music plays at
+    pub fn music(&self) -> f32 { self.master_volume * self.music_volume }
+
+    /// Volume sound effects play at
+    pub fn sfx(&self) -> f32 { self.master_volume * self.sfx_volume }
+
+    /// The bloom the game camera should have, if any
+    pub fn bloom_settings(&self) -> Option<BloomSettings> {
+        self.bloom.then(|| BloomSettings { intensity: self.bloom_intensity, ..default() })
+    }
+}
+
+/// One row of the settings screen
+#[derive(Clone, Copy)]
+enum Setting {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    Vsync,
+    Bloom,
+    BloomIntensity,
+    ScreenShake,
+    Difficulty,
+}
+
+impl Setting {
+    const ALL: [Setting; 9] = [
+        Setting::MasterVolume, Setting::MusicVolume, Setting::SfxVolume, Setting::Fullscreen, Setting::Vsync,
+        Setting::Bloom, Setting::BloomIntensity, Setting::ScreenShake, Setting::Difficulty,
+    ];
+
+    fn label(&self, settings: &Settings) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match self {
+            Setting::MasterVolume => format!("Master Volume: {:.0}%", settings.master_volume * 100.),
+            Setting::MusicVolume => format!("Music Volume: {:.0}%", settings.music_volume * 100.),
+            Setting::SfxVolume => format!("Sound Effects Volume: {:.0}%", settings.sfx_volume * 100.),
+            Setting::Fullscreen => format!("Fullscreen: {}", on_off(settings.fullscreen)),
+            Setting::Vsync => format!("VSync: {}", on_off(settings.vsync)),
+            Setting::Bloom => format!("Bloom: {}", on_off(settings.bloom)),
+            Setting::BloomIntensity => format!("Bloom Intensity: {:.2}", settings.bloom_intensity),
+            Setting::ScreenShake => format!("Screen Shake: {}", on_off(settings.screen_shake)),
+            Setting::Difficulty => format!("Difficulty: {:?}", settings.difficulty),
+        }
+    }
+
+    /// Move the setting on by one step, wrapping around at the end
+    fn step(&self, settings: &mut Settings) {
+        let volume = |v: f32| if v >= 1. - VOLUME_STEP / 2. { 0. } else { ((v + VOLUME_STEP) * 10.).round() / 10. };
+        match self {
+            Setting::MasterVolume => settings.master_volume = volume(settings.master_volume),
+            Setting::MusicVolume => settings.music_volume = volume(settings.music_volume),
+            Setting::SfxVolume => settings.sfx_volume = volume(settings.sfx_volume),
+            Setting::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            Setting::Vsync => settings.vsync = !settings.vsync,
+            Setting::Bloom => settings.bloom = !settings.bloom,
+            Setting::BloomIntensity => {
+                let next = ((settings.bloom_intensity + BLOOM_STEP) * 100.).round() / 100.;
+                settings.bloom_intensity = if next > MAX_BLOOM + BLOOM_STEP / 2. { BLOOM_STEP } else { next };
+            },
+            Setting::ScreenShake => settings.screen_shake = !settings.screen_shake,
+            Setting::Difficulty => settings.difficulty = settings.difficulty.next(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct SettingButton(Setting);
+
+pub fn settings_plugin(app: &mut App) {
+    app
+        .insert_resource(Settings::load())
+        .add_systems(OnEnter(MenuState::Settings), spawn_settings)
+        .add_systems(Update, (
+            (apply_video, apply_difficulty).run_if(resource_changed::<Settings>),
+            open_settings.run_if(in_state(MenuState::Main)),
+            (close_settings, setting_buttons, update_setting_text).chain().run_if(in_state(MenuState::Settings))
+        ));
+}
+
+/// Window mode and vsync (also how the saved settings get applied at startup)
+fn apply_video(settings: Res<Settings>, mut window: Query<&mut Window, With<PrimaryWindow>>){
+    let Ok(mut window) = window.get_single_mut() else { return; };
+    window.mode = if settings.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed };
+    window.present_mode = if settings.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync };
+}
+
+/// Runs pick up the difficulty when they start (played back runs use the one they were recorded with)
+fn apply_difficulty(settings: Res<Settings>, mut difficulty: ResMut<Difficulty>){
+    *difficulty = settings.difficulty;
+}
+
+fn open_settings(keyboard_input: Res<ButtonInput<KeyCode>>, mut menu_state: ResMut<NextState<MenuState>>){
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        menu_state.set(MenuState::Settings);
+    }
+}
+
+fn close_settings(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<Settings>, mut menu_state: ResMut<NextState<MenuState>>){
+    if keyboard_input.any_just_pressed([KeyCode::KeyO, KeyCode::Escape]) {
+        menu_state.set(MenuState::Main);
+    }
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        *settings = Settings::default();
+        settings.save();
+    }
+}
+
+fn setting_buttons(
+    mut interaction_query: Query<(&Interaction, &SettingButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut settings: ResMut<Settings>
+){
+    for (interaction, button, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                button.0.step(&mut settings);
+                settings.save();
+            }
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+}
+
+fn update_setting_text(settings: Res<Settings>, buttons: Query<(&SettingButton, &Children)>, mut text_query: Query<&mut Text>){
+    if !settings.is_changed() { return; }
+
+    for (button, children) in &buttons {
+        let Ok(mut text) = text_query.get_mut(children[0]) else { continue; };
+        text.sections[0].value = button.0.label(&settings);
+    }
+}
+
+fn spawn_settings(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<Settings>){
+    let font = asset_server.load("fonts/EvilEmpire.otf");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                ..default()
+            },
+            MenuView
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Settings", TextStyle { font: font.clone(), font_size: 80.0, color: Color::GOLD }));
+
+            for setting in Setting::ALL {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(700.0),
+                                height: Val::Px(56.0),
+                                padding: UiRect::left(Val::Px(16.0)),
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        },
+                        SettingButton(setting)
+                    ))
+                    .with_children(|button_parent| {
+                        button_parent.spawn(TextBundle::from_section(
+                            setting.label(&settings),
+                            TextStyle { font: font.clone(), font_size: 36.0, color: Color::rgb(0.9, 0.9, 0.9) },
+                        ));
+                    });
+            }
+
+            parent.spawn(TextBundle::from_section(
+                "Click a setting to change it, Delete to reset everything, O or Escape to go back",
+                TextStyle { font: font.clone(), font_size: 30.0, color: Color::GRAY },
+            ));
+        });
+}
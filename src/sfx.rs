@@ -0,0 +1,131 @@
+use bevy::{audio::Volume, prelude::*};
+use rand::Rng;
+
+use crate::settings::Settings;
+
+/// Most the pitch of a repeated sound is nudged up or down
+const PITCH_VARIATION: f32 = 0.08;
+
+/// Sound effects the simulation can ask for
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sfx {
+    Laser,
+    Womp,
+    Hit,
+    ShieldHit,
+    Warp,
+}
+
+/// Sounds are mixed in groups, each at its own level under the sound effects volume
+#[derive(Clone, Copy)]
+enum SfxCategory {
+    /// Shots fired, by far the most frequent
+    Weapon,
+    /// Things getting hit or blowing up
+    Impact,
+    /// Waves, bosses and respawns arriving
+    Alert,
+}
+
+impl SfxCategory {
+    fn volume(&self) -> f32 {
+        match self {
+            SfxCategory::Weapon => 0.5,
+            SfxCategory::Impact => 0.8,
+            SfxCategory::Alert => 1.,
+        }
+    }
+}
+
+impl Sfx {
+    pub const ALL: [Sfx; 5] = [Sfx::Laser, Sfx::Womp, Sfx::Hit, Sfx::ShieldHit, Sfx::Warp];
+
+    pub fn asset(&self) -> &'static str {
+        match self {
+            Sfx::Laser => "sounds/laser.wav",
+            Sfx::Womp => "sounds/womp.wav",
+            Sfx::Hit => "sounds/hit.wav",
+            Sfx::ShieldHit => "sounds/shieldhit.wav",
+            Sfx::Warp => "sounds/warp.wav",
+        }
+    }
+
+    fn category(&self) -> SfxCategory {
+        match self {
+            Sfx::Laser | Sfx::Womp => SfxCategory::Weapon,
+            Sfx::Hit | Sfx::ShieldHit => SfxCategory::Impact,
+            Sfx::Warp => SfxCategory::Alert,
+        }
+    }
+
+    /// Most copies of the sound playing at once, anything asked for past that is dropped
+    fn max_instances(&self) -> usize {
+        match self {
+            Sfx::Laser | Sfx::Hit => 4,
+            Sfx::ShieldHit => 3,
+            Sfx::Womp | Sfx::Warp => 2,
+        }
+    }
+
+    /// Whether copies played over each other get a slightly different pitch (so rapid fire doesn't drone)
+    fn varies_pitch(&self) -> bool {
+        matches!(self, Sfx::Laser | Sfx::Hit | Sfx::ShieldHit)
+    }
+}
+
+/// Request a sound effect from the simulation (played by the windowed build, ignored headless)
+#[derive(Event)]
+pub struct PlaySfx(pub Sfx);
+
+/// Sounds loaded up front so playing one never has to go through the asset server
+#[derive(Resource)]
+struct SfxHandles([Handle<AudioSource>; 5]);
+
+/// A sound effect that is still playing (the entity goes away when it ends)
+#[derive(Component)]
+struct Playing(Sfx);
+
+pub fn make_plugin(app: &mut App){
+    app
+        .add_systems(Startup, load_sounds)
+        .add_systems(Update, play_sounds);
+}
+
+fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands.insert_resource(SfxHandles(Sfx::ALL.map(|sfx| asset_server.load(sfx.asset()))));
+}
+
+fn play_sounds(
+    mut sfx_events: EventReader<PlaySfx>,
+    mut commands: Commands,
+    handles: Res<SfxHandles>,
+    settings: Res<Settings>,
+    playing: Query<&Playing>
+){
+    let mut counts = [0; Sfx::ALL.len()];
+    for sound in &playing {
+        counts[sound.0 as usize] += 1;
+    }
+
+    for sfx in sfx_events.read() {
+        let count = &mut counts[sfx.0 as usize];
+        if *count >= sfx.0.max_instances() { continue; }
+
+        let speed = match *count > 0 && sfx.0.varies_pitch() {
+            true => 1. + rand::thread_rng().gen_range(-PITCH_VARIATION..PITCH_VARIATION),
+            false => 1.,
+        };
+        *count += 1;
+
+        commands.spawn((
+            AudioBundle {
+                source: handles.0[sfx.0 as usize].clone(),
+                // auto-despawn the entity when playback finishes
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new(settings.sfx() * sfx.0.category().volume()))
+                    .with_speed(speed)
+            },
+            Playing(sfx.0)
+        ));
+    }
+}
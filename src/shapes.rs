@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 
-use crate::gun::BulletBlueprint;
+use crate::{gun::BulletBlueprint, trajectory};
 
 const SQRT_3: f32 = 1.73205080757;
 
@@ -108,7 +108,7 @@ impl Default for ShapeGun {
             timer: Timer::new(Duration::from_secs_f32(10.), TimerMode::Once),
             reload_time: 10.,
             bloops: Vec::new(),
-            bullet: BulletBlueprint(1, |y| y*y, |_| 0., 0., true, 50),
+            bullet: BulletBlueprint(1, trajectory::path("0", "t*t"), 0., true, 50),
             bullet_size: 25
         }
 
@@ -178,6 +178,11 @@ impl ShapeGun {
         self.shots = if ((self.shots - 1) as i64) < 0 {0} else {self.shots - 1};
     }
 
+    /// One more shot, up to the max
+    pub fn add_shot(&mut self){
+        self.shots = (self.shots + 1).min(self.max_shots);
+    }
+
     pub fn reload(&mut self){
         self.shots = self.max_shots;
         self.timer.reset();
@@ -0,0 +1,128 @@
+use bevy::{math::bounding::Aabb2d, prelude::*, utils::HashMap};
+
+use crate::{bomb::Invulnerable, enemy, hitbox::{Hitbox, Volume}, player::PlayerControlled};
+
+const CELL_SIZE: f32 = 64.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+/// What kind of thing is in the grid, queries only look at one layer at a time
+pub enum Layer {
+    Player,
+    Enemy,
+    PlayerBullet,
+    EnemyBullet,
+    PowerUp,
+}
+
+/// Uniform grid over the play field so overlap checks only look at things in nearby cells.
+/// Cleared each fixed tick, layers are filled in as their entities finish moving for the tick
+#[derive(Resource, Default)]
+pub struct SpatialGrid {
+    entries: Vec<(Entity, Volume)>,
+    cells: HashMap<(Layer, IVec2), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        for cell in self.cells.values_mut() {
+            cell.clear(); // keep the allocations, the same cells get used every tick
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, layer: Layer, volume: Volume) {
+        let index = self.entries.len();
+        self.entries.push((entity, volume));
+
+        let (min, max) = cell_range(&volume.aabb());
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.cells.entry((layer, IVec2::new(x, y))).or_default().push(index);
+            }
+        }
+    }
+
+    /// Everything in `layer` overlapping `area`, in the order it was inserted
+    pub fn query(&self, layer: Layer, area: impl Into<Volume>) -> Vec<(Entity, Volume)> {
+        let area = area.into();
+        let mut found = Vec::new();
+
+        let (min, max) = cell_range(&area.aabb());
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                if let Some(cell) = self.cells.get(&(layer, IVec2::new(x, y))) {
+                    found.extend(cell.iter().copied().filter(|i| self.entries[*i].1.intersects(&area)));
+                }
+            }
+        }
+
+        // big things sit in several cells, insertion order keeps results the same as a plain loop over the query
+        found.sort_unstable();
+        found.dedup();
+        found.into_iter().map(|i| self.entries[i]).collect()
+    }
+}
+
+fn cell_range(bounds: &Aabb2d) -> (IVec2, IVec2) {
+    ((bounds.min / CELL_SIZE).floor().as_ivec2(), (bounds.max / CELL_SIZE).floor().as_ivec2())
+}
+
+/// Start the tick's grid with the player (who has just moved, and can't be hit while invulnerable) and the enemies (who move after the bullets)
+pub fn rebuild_spatial_grid(
+    mut grid: ResMut<SpatialGrid>,
+    player_query: Query<(Entity, &Transform, &Hitbox, Has<Invulnerable>), With<PlayerControlled>>,
+    enemy_query: Query<(Entity, &Transform, &Hitbox), With<enemy::Collider>>
+){
+    grid.clear();
+
+    for (entity, transform, hitbox, invulnerable) in &player_query {
+        if invulnerable { continue; }
+        grid.insert(entity, hitbox.layer, hitbox.volume(transform));
+    }
+    for (entity, transform, hitbox) in &enemy_query {
+        grid.insert(entity, hitbox.layer, hitbox.volume(transform));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::bounding::BoundingCircle;
+
+    use super::*;
+
+    fn circle(x: f32, y: f32, radius: f32) -> Volume {
+        BoundingCircle::new(Vec2::new(x, y), radius).into()
+    }
+
+    #[test]
+    fn big_things_are_found_once() {
+        let mut grid = SpatialGrid::default();
+        let big = Entity::from_raw(1);
+        let small = Entity::from_raw(2);
+        // spans 4x4 cells, and so does the query
+        grid.insert(big, Layer::Enemy, circle(0., 0., CELL_SIZE * 1.5));
+        grid.insert(small, Layer::Enemy, circle(CELL_SIZE * 0.5, CELL_SIZE * 0.5, 1.));
+
+        let found: Vec<Entity> = grid.query(Layer::Enemy, circle(0., 0., CELL_SIZE * 1.5)).into_iter().map(|(entity, _)| entity).collect();
+        assert_eq!(found, vec![big, small]);
+    }
+
+    #[test]
+    fn only_overlaps_in_the_asked_layer() {
+        let mut grid = SpatialGrid::default();
+        let near = Entity::from_raw(1);
+        let bullet = Entity::from_raw(2);
+        let far = Entity::from_raw(3);
+        grid.insert(near, Layer::Enemy, circle(0., 0., 10.));
+        grid.insert(bullet, Layer::EnemyBullet, circle(0., 0., 10.));
+        // same cell as the query, but too far to touch it
+        grid.insert(far, Layer::Enemy, circle(CELL_SIZE - 2., CELL_SIZE - 2., 1.));
+
+        let found: Vec<Entity> = grid.query(Layer::Enemy, circle(5., 5., 10.)).into_iter().map(|(entity, _)| entity).collect();
+        assert_eq!(found, vec![near]);
+
+        grid.clear();
+        assert!(grid.query(Layer::Enemy, circle(0., 0., 10.)).is_empty());
+    }
+}
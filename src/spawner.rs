@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    enemy::{Enemy, EnemyBundle, EnemyType}, enemy_defs::{EnemyDef, EnemyDefs, EnemyDefsHandle}, game::GameRng, playfield::Playfield
+};
+
+/// Seconds between a spawner calling in more minions
+const BROOD_INTERVAL: f32 = 6.;
+/// Most minions one spawner can have alive at once
+const MINIONS_PER_SPAWNER: usize = 4;
+/// Most minions alive at once over all spawners
+const MAX_MINIONS: usize = 12;
+/// How far from the spawner minions show up
+const BROOD_SPREAD: f32 = 60.;
+/// Spawners split into two on death until their children are this many generations down
+const MAX_GENERATION: u32 = 2;
+/// Each generation has this much of its parent's health, score and size
+const CHILD_SHARE: f32 = 0.6;
+
+/// Calls in minions every so often and splits in two when it dies
+#[derive(Component)]
+pub struct Brood {
+    /// What it was spawned from (children are weaker copies)
+    def: EnemyDef,
+    generation: u32,
+    minions: Vec<Entity>,
+    timer: Timer,
+}
+
+impl Brood {
+    fn new(def: EnemyDef, generation: u32) -> Brood {
+        Brood { def, generation, minions: Vec::new(), timer: Timer::new(Duration::from_secs_f32(BROOD_INTERVAL), TimerMode::Repeating) }
+    }
+}
+
+/// Enemy called in by a spawner
+#[derive(Component)]
+pub struct Minion;
+
+/// Give newly spawned spawners their brood (children get theirs when they split off)
+pub fn adopt_spawners(
+    mut commands: Commands,
+    spawners: Query<(Entity, &Enemy, Has<Brood>), Added<Enemy>>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>
+){
+    let Some(defs) = defs.get(&defs_handle.0) else { return; };
+
+    for (entity, enemy, has_brood) in &spawners {
+        if has_brood || !matches!(enemy.get_type(), EnemyType::Spawner) { continue; }
+        if let Some(def) = defs.get(enemy.get_name()) {
+            commands.entity(entity).insert(Brood::new(def.clone(), 0));
+        }
+    }
+}
+
+/// Spawners on screen call in small groups of other enemies around themselves, up to their own and the global cap
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_minions(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut spawners: Query<(&Transform, &mut Brood)>,
+    minions: Query<(), With<Minion>>,
+    mut rng: ResMut<GameRng>,
+    defs: Res<Assets<EnemyDefs>>,
+    defs_handle: Res<EnemyDefsHandle>,
+    playfield: Res<Playfield>
+){
+    let Some(defs) = defs.get(&defs_handle.0) else { return; };
+    let mut alive = minions.iter().count();
+
+    for (transform, mut brood) in &mut spawners {
+        if transform.translation.y > playfield.top { continue; }
+
+        brood.timer.tick(time.delta());
+        if !brood.timer.just_finished() { continue; }
+
+        brood.minions.retain(|minion| minions.contains(*minion));
+        let room = (MINIONS_PER_SPAWNER - brood.minions.len()).min(MAX_MINIONS.saturating_sub(alive));
+        let count = rng.0.gen_range(2..=3).min(room);
+        if count == 0 { continue; }
+
+        for _ in 0..count {
+            // spawners don't bring in more spawners, that only happens when they die
+            let Some(def) = defs.pick(&mut rng).filter(|def| !matches!(def.behavior, EnemyType::Spawner)) else { continue; };
+            let x = transform.translation.x + rng.0.gen_range(-BROOD_SPREAD..BROOD_SPREAD);
+            let y = transform.translation.y + rng.0.gen_range(-BROOD_SPREAD..BROOD_SPREAD);
+
+            let minion = commands.spawn((EnemyBundle::new(x, y, def).scaled(0.75), Minion)).id();
+            brood.minions.push(minion);
+            alive += 1;
+        }
+    }
+}
+
+/// Split a dead spawner into two weaker, smaller ones (unless it is already the last generation)
+pub fn split(commands: &mut Commands, brood: &Brood, position: Vec3) {
+    if brood.generation >= MAX_GENERATION { return; }
+
+    let generation = brood.generation + 1;
+    let scale = CHILD_SHARE.powi(generation as i32).sqrt();
+
+    let mut def = brood.def.clone();
+    def.health.health = (def.health.health as f32 * CHILD_SHARE) as i64;
+    def.health.shield = (def.health.shield as f32 * CHILD_SHARE) as i64;
+    def.score = (def.score as f32 * CHILD_SHARE) as u64;
+
+    for side in [-1., 1.] {
+        let x = position.x + side * 40.;
+        commands.spawn((EnemyBundle::new(x, position.y, &def).scaled(scale), Brood::new(def.clone(), generation)));
+    }
+}
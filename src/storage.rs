@@ -0,0 +1,100 @@
+use std::{env, fs, io::ErrorKind, path::PathBuf};
+
+use bevy::log::{info, warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+const APP_DIR: &str = "bullet_hell_elite";
+
+/// Where save files live: `$XDG_DATA_HOME` (or `~/.local/share`) on linux, `%APPDATA%` on windows,
+/// `~/Library/Application Support` on mac. Falls back to the working directory if none of those are set
+pub fn data_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+
+    base.unwrap_or_default().join(APP_DIR)
+}
+
+/// Read a RON file from the data dir. A missing file is `None`, so is a broken one, bad UTF-8 included
+/// (which gets moved to `<name>.corrupt` so saving over it doesn't lose it for good)
+pub fn load_ron<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let path = data_dir().join(name);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("could not read {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    let value = String::from_utf8(bytes)
+        .map_err(|e| e.to_string())
+        .and_then(|text| ron::from_str(&text).map_err(|e| e.to_string()));
+
+    match value {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("{} is corrupted ({}), starting over", path.display(), e);
+            let backup = path.with_extension("corrupt");
+            if fs::rename(&path, &backup).is_ok() {
+                info!("moved the old file to {}", backup.display());
+            }
+            None
+        }
+    }
+}
+
+/// Write a RON file to the data dir (through a temp file so a crash mid write can't corrupt it)
+pub fn save_ron<T: Serialize>(name: &str, value: &T) {
+    let dir = data_dir();
+    let path = dir.join(name);
+    let temp = dir.join(format!("{}.tmp", name));
+
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|text| {
+            fs::create_dir_all(&dir)
+                .and_then(|_| fs::write(&temp, text))
+                .and_then(|_| fs::rename(&temp, &path))
+                .map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        warn!("could not save {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the only test touching the data dir, so pointing it somewhere else can't race another one
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn broken_files_are_moved_aside() {
+        let base = env::temp_dir().join(format!("{}_test_{}", APP_DIR, std::process::id()));
+        env::set_var("XDG_DATA_HOME", &base);
+        let dir = data_dir();
+        fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(load_ron::<u32>("missing.ron"), None);
+
+        save_ron("fine.ron", &7u32);
+        assert_eq!(load_ron::<u32>("fine.ron"), Some(7));
+
+        for (name, bytes) in [("bad_ron.ron", &b"(("[..]), ("bad_utf8.ron", &[0xff, 0xfe, b'7'][..])] {
+            fs::write(dir.join(name), bytes).unwrap();
+            assert_eq!(load_ron::<u32>(name), None);
+            assert!(!dir.join(name).exists());
+            assert_eq!(fs::read(dir.join(name).with_extension("corrupt")).unwrap(), bytes);
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
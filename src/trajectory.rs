@@ -0,0 +1,432 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use bevy::math::Vec2;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A math expression of the time `t` (eg `9 * cos(10 * t)`), parsed from text and cheap to clone
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr(Arc<Node>);
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    Const(f32),
+    Time,
+    Neg(Expr),
+    Add(Expr, Expr),
+    Sub(Expr, Expr),
+    Mul(Expr, Expr),
+    Div(Expr, Expr),
+    Pow(Expr, Expr),
+    Call(Func, Expr),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Func {
+    Sin,
+    Cos,
+    Tan,
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Func> {
+        match name {
+            "sin" => Some(Func::Sin),
+            "cos" => Some(Func::Cos),
+            "tan" => Some(Func::Tan),
+            "abs" => Some(Func::Abs),
+            "sqrt" => Some(Func::Sqrt),
+            "exp" => Some(Func::Exp),
+            "ln" => Some(Func::Ln),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Func::Sin => "sin",
+            Func::Cos => "cos",
+            Func::Tan => "tan",
+            Func::Abs => "abs",
+            Func::Sqrt => "sqrt",
+            Func::Exp => "exp",
+            Func::Ln => "ln",
+        }
+    }
+
+    fn apply(&self, v: f32) -> f32 {
+        match self {
+            Func::Sin => v.sin(),
+            Func::Cos => v.cos(),
+            Func::Tan => v.tan(),
+            Func::Abs => v.abs(),
+            Func::Sqrt => v.sqrt(),
+            Func::Exp => v.exp(),
+            Func::Ln => v.ln(),
+        }
+    }
+}
+
+impl Expr {
+    pub fn constant(value: f32) -> Expr {
+        Expr(Arc::new(Node::Const(value)))
+    }
+
+    /// Parse an expression, any name other than `t`, `pi` or a function is looked up in `params`
+    pub fn parse_with(text: &str, params: &HashMap<String, f32>) -> Result<Expr, ParseError> {
+        let mut parser = Parser { text, at: 0, params };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.at < text.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(expr)
+    }
+
+    pub fn parse(text: &str) -> Result<Expr, ParseError> {
+        Expr::parse_with(text, &HashMap::new())
+    }
+
+    /// Evaluate at time `t`
+    pub fn eval(&self, t: f32) -> f32 {
+        match self.0.as_ref() {
+            Node::Const(v) => *v,
+            Node::Time => t,
+            Node::Neg(a) => -a.eval(t),
+            Node::Add(a, b) => a.eval(t) + b.eval(t),
+            Node::Sub(a, b) => a.eval(t) - b.eval(t),
+            Node::Mul(a, b) => a.eval(t) * b.eval(t),
+            Node::Div(a, b) => a.eval(t) / b.eval(t),
+            Node::Pow(a, b) => a.eval(t).powf(b.eval(t)),
+            Node::Call(f, a) => f.apply(a.eval(t)),
+        }
+    }
+
+    fn node(node: Node) -> Expr {
+        // fold anything that doesn't depend on t so it isn't recomputed every tick
+        let expr = Expr(Arc::new(node));
+        if expr.is_constant() { Expr::constant(expr.eval(0.)) } else { expr }
+    }
+
+    fn is_constant(&self) -> bool {
+        match self.0.as_ref() {
+            Node::Const(_) => true,
+            Node::Time => false,
+            Node::Neg(a) | Node::Call(_, a) => a.is_constant(),
+            Node::Add(a, b) | Node::Sub(a, b) | Node::Mul(a, b) | Node::Div(a, b) | Node::Pow(a, b) => a.is_constant() && b.is_constant(),
+        }
+    }
+}
+
+/// Parse a hardcoded expression, panics if it isn't valid (only use with literals)
+pub fn expr(text: &str) -> Expr {
+    Expr::parse(text).unwrap_or_else(|e| panic!("invalid expression {:?}: {}", text, e))
+}
+
+/// Parse hardcoded x / y expressions, panics if they aren't valid (only use with literals)
+pub fn path(x: &str, y: &str) -> Trajectory {
+    Trajectory::new(expr(x), expr(y))
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.as_ref() {
+            // a bare negative number as a power's base would read back as the negation of the power
+            Node::Const(v) if *v < 0. => write!(f, "({})", v),
+            Node::Const(v) => write!(f, "{}", v),
+            Node::Time => write!(f, "t"),
+            Node::Neg(a) => write!(f, "-({})", a),
+            Node::Add(a, b) => write!(f, "({} + {})", a, b),
+            Node::Sub(a, b) => write!(f, "({} - {})", a, b),
+            Node::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Node::Div(a, b) => write!(f, "({} / {})", a, b),
+            Node::Pow(a, b) => write!(f, "({} ^ {})", a, b),
+            Node::Call(func, a) => write!(f, "{}({})", func.name(), a),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError { pub message: String, pub position: usize }
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Recursive descent over `+ -`, `* /`, unary `-`, `^` (right associative), calls, names and numbers
+struct Parser<'a> { text: &'a str, at: usize, params: &'a HashMap<String, f32> }
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { message: message.to_string(), position: self.at }
+    }
+
+    /// Fold the node, constants that come out infinite or NaN (eg `ln(0)`) can't be written back out so they are an error
+    fn node(&self, node: Node) -> Result<Expr, ParseError> {
+        let expr = Expr::node(node);
+        match expr.0.as_ref() {
+            Node::Const(v) if !v.is_finite() => Err(self.error("not a finite number")),
+            _ => Ok(expr),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.text[self.at..].chars().next().filter(|c| c.is_whitespace()) {
+            self.at += c.len_utf8(); // not all whitespace is one byte
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text[self.at..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.term()?;
+        loop {
+            if self.eat('+') { let rhs = self.term()?; lhs = self.node(Node::Add(lhs, rhs))?; }
+            else if self.eat('-') { let rhs = self.term()?; lhs = self.node(Node::Sub(lhs, rhs))?; }
+            else { return Ok(lhs); }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            if self.eat('*') { let rhs = self.unary()?; lhs = self.node(Node::Mul(lhs, rhs))?; }
+            else if self.eat('/') { let rhs = self.unary()?; lhs = self.node(Node::Div(lhs, rhs))?; }
+            else { return Ok(lhs); }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat('-') {
+            let inner = self.unary()?;
+            return self.node(Node::Neg(inner));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            return self.node(Node::Pow(base, exponent));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some('(') => {
+                self.at += 1;
+                let inner = self.expr()?;
+                if !self.eat(')') { return Err(self.error("expected )")); }
+                Ok(inner)
+            },
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.at;
+                while self.text[self.at..].starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+                    self.at += 1;
+                }
+                self.text[start..self.at].parse().map(Expr::constant).map_err(|_| ParseError { message: "bad number".to_string(), position: start })
+            },
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.at;
+                while self.text[self.at..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+                    self.at += 1;
+                }
+                let name = &self.text[start..self.at];
+
+                if let Some(func) = Func::from_name(name) {
+                    if !self.eat('(') { return Err(self.error("expected ( after function")); }
+                    let arg = self.expr()?;
+                    if !self.eat(')') { return Err(self.error("expected )")); }
+                    return self.node(Node::Call(func, arg));
+                }
+
+                match name {
+                    "t" => Ok(Expr(Arc::new(Node::Time))),
+                    "pi" => Ok(Expr::constant(std::f32::consts::PI)),
+                    _ => match self.params.get(name) {
+                        Some(v) if v.is_finite() => Ok(Expr::constant(*v)),
+                        Some(_) => Err(ParseError { message: format!("{} is not a finite number", name), position: start }),
+                        None => Err(ParseError { message: format!("unknown name {}", name), position: start }),
+                    },
+                }
+            },
+            _ => Err(self.error("expected a number, name or (")),
+        }
+    }
+}
+
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Expr, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Expr::parse(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parametric x(t) / y(t) pair, for bullets and enemy paths this is the distance moved each tick
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Trajectory {
+    pub x: Expr,
+    pub y: Expr,
+}
+
+impl Trajectory {
+    pub fn new(x: Expr, y: Expr) -> Trajectory {
+        Trajectory { x, y }
+    }
+
+    pub fn eval(&self, t: f32) -> Vec2 {
+        Vec2::new(self.x.eval(t), self.y.eval(t))
+    }
+}
+
+/// How a trajectory is written in data files, `params` are named constants the expressions can use
+/// eg `(x: "amp * cos(freq * t)", y: "5", params: {"amp": 9, "freq": 10})`
+#[derive(Deserialize)]
+struct TrajectoryDef {
+    x: String,
+    y: String,
+    #[serde(default)]
+    params: HashMap<String, f32>,
+}
+
+impl<'de> Deserialize<'de> for Trajectory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Trajectory, D::Error> {
+        let def = TrajectoryDef::deserialize(deserializer)?;
+        let x = Expr::parse_with(&def.x, &def.params).map_err(serde::de::Error::custom)?;
+        let y = Expr::parse_with(&def.y, &def.params).map_err(serde::de::Error::custom)?;
+        Ok(Trajectory { x, y })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, t: f32) -> f32 {
+        Expr::parse(text).unwrap().eval(t)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3", 0.), 7.);
+        assert_eq!(eval("(1 + 2) * 3", 0.), 9.);
+        assert_eq!(eval("8 / 4 / 2", 0.), 1.);
+        assert_eq!(eval("10 - 4 - 3", 0.), 3.);
+        assert_eq!(eval("2 ^ 3 ^ 2", 0.), 512.); // right associative
+        assert_eq!(eval("2 * 3 ^ 2", 0.), 18.);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-3", 0.), -3.);
+        assert_eq!(eval("- -3", 0.), 3.);
+        assert_eq!(eval("2 * -t", 4.), -8.);
+        assert_eq!(eval("-2 ^ 2", 0.), -4.); // the power binds tighter
+        assert_eq!(eval("2 ^ -1", 0.), 0.5);
+    }
+
+    #[test]
+    fn function_calls() {
+        assert_eq!(eval("sin(0)", 0.), 0.);
+        assert_eq!(eval("cos(pi)", 0.), -1.);
+        assert_eq!(eval("abs(-t)", 2.5), 2.5);
+        assert_eq!(eval("sqrt(abs(t - 20))", 4.), 4.);
+        assert_eq!(eval("exp(ln(t))", 3.).round(), 3.);
+    }
+
+    #[test]
+    fn evaluates_a_path() {
+        let expr = Expr::parse("9*cos(t*10) + 0.5 * t").unwrap();
+        let t = 0.3;
+        assert!((expr.eval(t) - (9. * (t * 10.).cos() + 0.5 * t)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn params() {
+        let params = HashMap::from([("amp".to_string(), 3.), ("freq".to_string(), 2.)]);
+        assert_eq!(Expr::parse_with("amp * freq + t", &params).unwrap().eval(1.), 7.);
+        assert!(Expr::parse("amp * t").is_err());
+    }
+
+    #[test]
+    fn constants_are_folded() {
+        assert_eq!(Expr::parse("2 * 3 + 1").unwrap(), Expr::constant(7.));
+        assert_eq!(Expr::parse("cos(0) * 4").unwrap(), Expr::constant(4.));
+        assert_ne!(Expr::parse("2 * t").unwrap(), Expr::constant(0.));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for text in ["", "1 +", "(1 + 2", "sin 1", "sin(1", "1 2", "foo(t)", "t)", "1..2", "*3", "\u{e9}"] {
+            assert!(Expr::parse(text).is_err(), "{:?} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn unicode_whitespace() {
+        assert_eq!(eval("1\u{a0}+\u{3000}t", 2.), 3.);
+        assert!(Expr::parse("t\u{3000}").is_ok());
+        assert!(Expr::parse("\u{3000}\u{e9}").is_err());
+    }
+
+    #[test]
+    fn display_parses_back() {
+        for text in ["9*cos(t*10)", "-t ^ 2 / (1 + abs(t))", "2 ^ 3 ^ t", "sqrt(t) - -t", "(0 - 2) ^ t", "(-2) ^ t * -1"] {
+            let expr = Expr::parse(text).unwrap();
+            let again = Expr::parse(&expr.to_string()).unwrap();
+            assert_eq!(again.to_string(), expr.to_string());
+            for t in [0., 0.5, 2.] {
+                let (a, b) = (expr.eval(t), again.eval(t));
+                assert!(a == b || (a.is_nan() && b.is_nan()), "{} vs {} at {}: {} vs {}", text, expr, t, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn non_finite_constants_are_errors() {
+        for text in ["ln(0)", "1 / 0 + t", "sqrt(-1) * t", "t ^ (0 / 0)"] {
+            assert!(Expr::parse(text).is_err(), "{:?} should not parse", text);
+        }
+        let params = HashMap::from([("big".to_string(), f32::INFINITY)]);
+        assert!(Expr::parse_with("big * t", &params).is_err());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let path = Trajectory::new(expr("5*cos(t*5)"), expr("t*t"));
+        let text = ron::to_string(&path).unwrap();
+        let back: Trajectory = ron::from_str(&text).unwrap();
+        assert_eq!(back.eval(1.5), path.eval(1.5));
+
+        let with_params: Trajectory = ron::from_str(r#"(x: "amp * t", y: "1", params: {"amp": 2.0})"#).unwrap();
+        assert_eq!(with_params.eval(3.), Vec2::new(6., 1.));
+        assert!(ron::from_str::<Trajectory>(r#"(x: "amp * t", y: "1")"#).is_err());
+    }
+}